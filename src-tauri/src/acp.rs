@@ -1,9 +1,30 @@
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::io::{BufRead, Write};
-use std::process::{Child, ChildStdout, Command, Stdio};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex as StdMutex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, PoisonError};
 use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::client_fs;
+use crate::error::AppError;
+use crate::protocol::{
+    AcpRequest, AgentCapabilities, CancelNotification, ClientCapabilities, ContentBlock, FileSystemCapability,
+    Implementation, IncomingMessage, InitializeRequest, InitializeResponse, LoadSessionRequest,
+    McpServer, NewSessionRequest, Notification, PermissionOption, PermissionOptionKind,
+    PromptRequest, PromptResponse, Request, RequestId, RequestPermissionOutcome,
+    RequestPermissionRequest, RequestPermissionResponse, Response, RpcError, SessionModeState,
+    SessionModelState, SessionNotification, SessionUpdate, SetSessionModeRequest,
+    SetSessionModelRequest, ToolKind, PROTOCOL_VERSION, REQUEST_PERMISSION, SESSION_UPDATE,
+};
+use crate::trace::{Direction, Trace};
+
+pub use crate::protocol::StopReason;
 
 #[cfg(unix)]
 extern crate libc;
@@ -12,223 +33,996 @@ fn log(msg: &str) {
     eprintln!("[ACP] {}", msg);
 }
 
-type SharedStdin = Arc<StdMutex<std::process::ChildStdin>>;
+/// The agent's stdin; every message written is also recorded in the trace.
+struct Outgoing {
+    stdin: Mutex<ChildStdin>,
+    trace: Option<Arc<Trace>>,
+}
+
+type SharedStdin = Arc<Outgoing>;
+/// Requests waiting for a response, keyed by JSON-RPC id.
+type Pending = Arc<StdMutex<HashMap<u64, oneshot::Sender<Result<Value, AppError>>>>>;
+/// Per-session notification streams, keyed by session id.
+type Subscribers = Arc<StdMutex<HashMap<String, mpsc::UnboundedSender<IncomingMessage>>>>;
+/// The last lines the agent wrote to stderr.
+type StderrTail = Arc<StdMutex<VecDeque<String>>>;
+
+/// Lines of agent stderr kept for crash reports
+pub const STDERR_TAIL_LINES: usize = 200;
+/// How often `wait_exit` checks whether the process is still running
+const EXIT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Time the agent gets to exit on SIGTERM before it is killed
+const KILL_GRACE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Receives the events a prompt turn produces: the Tauri app at runtime, a recorder in tests.
+pub trait EventSink: Send + Sync {
+    fn emit_event(&self, event: &str, payload: Value);
+}
+
+impl EventSink for AppHandle {
+    fn emit_event(&self, event: &str, payload: Value) {
+        let _ = self.emit(event, payload);
+    }
+}
+
+/// How `session/request_permission` is answered for one tool kind
+/// (`read`, `edit`, `execute`, ...).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionPolicy {
+    Ask,
+    AutoAllow,
+    AutoDeny,
+}
+
+/// Result of one prompt turn.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptOutcome {
+    pub text: String,
+    /// `None` when the process went away before the turn finished
+    pub stop_reason: Option<StopReason>,
+    /// `usage` from the `session/prompt` result, as reported by the agent
+    pub usage: Option<Value>,
+    /// `_meta` from the `session/prompt` result (credits and other agent extensions)
+    pub meta: Option<Value>,
+    pub duration_ms: u64,
+    pub tool_call_count: usize,
+    pub chunk_count: usize,
+}
+
+/// How long to wait on the agent, in seconds; 0 waits forever.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Timeouts {
+    pub initialize_secs: u64,
+    pub new_session_secs: u64,
+    /// Loading replays the whole conversation, so it gets longer
+    pub load_session_secs: u64,
+    /// `session/set_model` and `session/set_mode`
+    pub set_model_secs: u64,
+    /// A running prompt that streams nothing for this long is reported as `acp-stalled`
+    pub prompt_inactivity_secs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            initialize_secs: 30,
+            new_session_secs: 60,
+            load_session_secs: 120,
+            set_model_secs: 15,
+            prompt_inactivity_secs: 120,
+        }
+    }
+}
+
+/// Models and modes the agent offers for a session, with the ones currently selected.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCapabilities {
+    /// `None` when the agent does not support switching models
+    pub models: Option<SessionModelState>,
+    /// `None` when the agent has no modes
+    pub modes: Option<SessionModeState>,
+}
+
+/// State of the agent process behind a session, for troubleshooting.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub pid: Option<u32>,
+    pub running: bool,
+    pub exit_status: Option<String>,
+    /// Last lines the agent wrote to stderr, oldest first
+    pub stderr: Vec<String>,
+    /// JSON lines file with every message exchanged, see `trace.rs`
+    pub trace_path: Option<String>,
+    pub agent_info: Option<Implementation>,
+}
+
+/// What a turn has streamed so far.
+#[derive(Default)]
+struct TurnProgress {
+    text: String,
+    chunk_count: usize,
+    tool_call_count: usize,
+    /// Permission requests may omit the kind, so remember it from the tool call
+    tool_kinds: HashMap<String, ToolKind>,
+}
+
+/// The prompt currently running on a session.
+struct Turn {
+    request_id: u64,
+    cancel_requested: bool,
+}
+
+/// MCP servers attached to every session: this app's own binary in `--mcp-server`
+/// mode, scoped to the workspace the session runs in.
+fn mcp_servers(cwd: &str) -> Vec<McpServer> {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            log(&format!("Cannot locate app binary, no MCP server attached: {}", e));
+            return Vec::new();
+        }
+    };
+    vec![McpServer {
+        name: "kiro-notebook-files".to_string(),
+        command: exe.display().to_string(),
+        args: vec!["--mcp-server".to_string(), cwd.to_string()],
+        env: Vec::new(),
+    }]
+}
+
+/// How to start an agent process.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AgentCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Added to the app's environment
+    pub env: HashMap<String, String>,
+    /// Where the process starts; the workspace when `None`
+    pub working_dir: Option<String>,
+}
+
+impl AgentCommand {
+    /// `<kiro_cli_path> acp`
+    pub fn kiro(kiro_cli_path: &str) -> Self {
+        Self {
+            program: kiro_cli_path.to_string(),
+            args: vec!["acp".to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// The program's file name, used to tag its stderr in our logs
+    pub fn label(&self) -> String {
+        Path::new(&self.program)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.program.clone())
+    }
+}
 
 pub struct AcpClient {
-    child: Child,
+    /// Taken by `kill`, which hands it to the task that stops and reaps it
+    child: StdMutex<Option<Child>>,
+    pid: Option<u32>,
+    trace: Option<Arc<Trace>>,
     stdin: SharedStdin,
-    stdout_lines: std::io::Lines<std::io::BufReader<ChildStdout>>,
+    pending: Pending,
+    subscribers: Subscribers,
+    /// Set once stdout hits EOF
+    closed: watch::Receiver<bool>,
+    killed: AtomicBool,
+    reader: JoinHandle<()>,
+    stderr_reader: JoinHandle<()>,
+    stderr_tail: StderrTail,
+    /// Permission requests forwarded to the UI and not yet answered
+    open_permissions: StdMutex<HashSet<RequestId>>,
+    /// In-flight prompt per session id
+    turns: StdMutex<HashMap<String, Turn>>,
+    /// The `initialize` response, once it has run
+    agent: StdMutex<Option<InitializeResponse>>,
+    /// From the `session/new` or `session/load` response, kept current as the selection changes
+    capabilities: StdMutex<SessionCapabilities>,
     request_id: AtomicU64,
+    timeouts: Timeouts,
     pub session_id: Option<String>,
 }
 
 impl AcpClient {
-    pub fn spawn(kiro_cli_path: &str) -> Result<Self, String> {
-        log(&format!("Spawning: {}", kiro_cli_path));
-        let mut cmd = Command::new(kiro_cli_path);
-        cmd.arg("acp")
+    /// Spawn `kiro-cli acp` in `cwd`; `fs/*` requests from it are served relative to that workspace.
+    pub fn spawn(kiro_cli_path: &str, cwd: &str) -> Result<Self, AppError> {
+        Self::spawn_command(&AgentCommand::kiro(kiro_cli_path), cwd)
+    }
+
+    /// Spawn `command` for the workspace `cwd`.
+    pub fn spawn_command(command: &AgentCommand, cwd: &str) -> Result<Self, AppError> {
+        log(&format!("Spawning: {} {}", command.program, command.args.join(" ")));
+        let mut cmd = Command::new(&command.program);
+        cmd.args(&command.args)
+            .envs(&command.env)
+            .current_dir(command.working_dir.as_deref().unwrap_or(cwd))
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit());
+            .stderr(Stdio::piped());
 
         #[cfg(unix)]
-        {
-            use std::os::unix::process::CommandExt;
-            cmd.process_group(0);
-        }
+        cmd.process_group(0);
 
         let mut child = cmd.spawn()
-            .map_err(|e| format!("Failed to spawn kiro-cli acp: {}", e))?;
+            .map_err(|e| AppError::SpawnFailed { program: command.program.clone(), message: e.to_string() })?;
+
+        let pid = child.id();
+        log(&format!("Spawned pid: {}", pid.unwrap_or_default()));
+        let no_pipe = |name: &str| AppError::SpawnFailed { program: command.program.clone(), message: format!("no {} pipe", name) };
+        let stdin = child.stdin.take().ok_or_else(|| no_pipe("stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| no_pipe("stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| no_pipe("stderr"))?;
 
-        log(&format!("Spawned pid: {}", child.id()));
-        let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
-        let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
-        let reader = std::io::BufReader::new(stdout);
+        // Without a trace the session still works; it is just harder to debug
+        let trace = match Trace::create(std::path::Path::new(cwd), pid.unwrap_or_default()) {
+            Ok(trace) => Some(Arc::new(trace)),
+            Err(e) => {
+                log(&format!("No protocol trace: {}", e));
+                None
+            }
+        };
+        let stdin = Arc::new(Outgoing { stdin: Mutex::new(stdin), trace: trace.clone() });
+        let pending: Pending = Arc::new(StdMutex::new(HashMap::new()));
+        let subscribers: Subscribers = Arc::new(StdMutex::new(HashMap::new()));
+        let (closed_tx, closed) = watch::channel(false);
+        let workspace = std::fs::canonicalize(cwd).ok();
+        let reader = tokio::spawn(read_loop(
+            stdout,
+            stdin.clone(),
+            pending.clone(),
+            subscribers.clone(),
+            closed_tx,
+            workspace,
+            trace.clone(),
+        ));
+        let stderr_tail: StderrTail = Arc::new(StdMutex::new(VecDeque::new()));
+        let stderr_reader = tokio::spawn(read_stderr(stderr, command.label(), stderr_tail.clone()));
 
         Ok(Self {
-            child,
-            stdin: Arc::new(StdMutex::new(stdin)),
-            stdout_lines: reader.lines(),
+            child: StdMutex::new(Some(child)),
+            pid,
+            trace,
+            stdin,
+            pending,
+            subscribers,
+            closed,
+            killed: AtomicBool::new(false),
+            reader,
+            stderr_reader,
+            stderr_tail,
+            open_permissions: StdMutex::new(HashSet::new()),
+            turns: StdMutex::new(HashMap::new()),
+            agent: StdMutex::new(None),
+            capabilities: StdMutex::new(SessionCapabilities::default()),
             request_id: AtomicU64::new(0),
+            timeouts: Timeouts::default(),
             session_id: None,
         })
     }
 
-    pub fn cancel_handle(&self) -> SharedStdin {
-        self.stdin.clone()
-    }
-
     fn next_id(&self) -> u64 {
         self.request_id.fetch_add(1, Ordering::SeqCst)
     }
 
-    fn write_request(&self, id: u64, method: &str, params: Value) -> Result<(), String> {
-        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
-        let msg = serde_json::to_string(&request).unwrap();
+    async fn write_request<P: Serialize>(&self, id: u64, method: &str, params: P) -> Result<(), AppError> {
         log(&format!(">>> [id={}] {}", id, method));
-        let mut stdin = self.stdin.lock().map_err(|e| format!("Lock error: {}", e))?;
-        stdin.write_all(format!("{}\n", msg).as_bytes()).map_err(|e| format!("Write error: {}", e))?;
-        stdin.flush().map_err(|e| format!("Flush error: {}", e))
+        write_message(&self.stdin, &Request::new(RequestId::Number(id), method, params)).await
     }
 
-    fn send_request(&mut self, method: &str, params: Value) -> Result<Value, String> {
-        let id = self.next_id();
-        self.write_request(id, method, params)?;
-        self.read_response(id)
+    /// Register `id` as pending before writing, so the reader can never see the
+    /// response before there is someone to hand it to.
+    fn register(&self, id: u64) -> Result<oneshot::Receiver<Result<Value, AppError>>, AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner).insert(id, tx);
+        if self.is_closed() {
+            self.unregister(id);
+            return Err(AppError::ProcessClosed);
+        }
+        Ok(rx)
     }
 
-    fn read_response(&mut self, expected_id: u64) -> Result<Value, String> {
-        loop {
-            match self.stdout_lines.next() {
-                Some(Ok(line)) => {
-                    if line.trim().is_empty() { continue; }
-                    let Ok(msg) = serde_json::from_str::<Value>(&line) else { continue };
-                    if msg.get("id").is_none() { continue; }
-                    if let Some(id) = msg.get("id").and_then(|v| v.as_u64()) {
-                        if id == expected_id {
-                            if let Some(error) = msg.get("error") {
-                                return Err(format!("ACP error: {}", error));
-                            }
-                            return Ok(msg.get("result").cloned().unwrap_or(Value::Null));
-                        }
-                    }
+    fn unregister(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+
+    /// Use `timeouts` instead of the defaults.
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Send a request and wait up to `seconds` (0: forever) for its response.
+    async fn send_request<R: AcpRequest>(&self, params: &R, seconds: u64) -> Result<R::Response, AppError> {
+        let id = self.next_id();
+        let rx = self.register(id)?;
+        if let Err(e) = self.write_request(id, R::METHOD, params).await {
+            self.unregister(id);
+            return Err(e);
+        }
+        let received = if seconds == 0 {
+            rx.await
+        } else {
+            match tokio::time::timeout(std::time::Duration::from_secs(seconds), rx).await {
+                Ok(received) => received,
+                Err(_) => {
+                    self.unregister(id);
+                    log(&format!("{} [id={}] timed out after {}s", R::METHOD, id, seconds));
+                    return Err(AppError::Timeout { method: R::METHOD.to_string(), seconds });
                 }
-                Some(Err(e)) => return Err(format!("Read error: {}", e)),
-                None => return Err("ACP process closed".to_string()),
             }
+        };
+        let result = received.map_err(|_| AppError::ProcessClosed)??;
+        serde_json::from_value(result)
+            .map_err(|e| AppError::InvalidResponse { method: R::METHOD.to_string(), message: e.to_string() })
+    }
+
+    /// Route every notification for `session_id` to the returned receiver until
+    /// `unsubscribe` is called or the process exits.
+    fn subscribe(&self, session_id: &str) -> Result<mpsc::UnboundedReceiver<IncomingMessage>, AppError> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap_or_else(PoisonError::into_inner)
+            .insert(session_id.to_string(), tx);
+        Ok(rx)
+    }
+
+    fn unsubscribe(&self, session_id: &str) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.remove(session_id);
         }
     }
 
-    pub fn initialize(&mut self) -> Result<Value, String> {
+    pub async fn initialize(&self) -> Result<InitializeResponse, AppError> {
+        self.initialize_as(Some(Implementation {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            title: None,
+        })).await
+    }
+
+    /// `initialize`, introducing this client as `client_info`.
+    pub async fn initialize_as(&self, client_info: Option<Implementation>) -> Result<InitializeResponse, AppError> {
         log("Initializing...");
-        self.send_request("initialize", json!({
-            "protocolVersion": 1,
-            "clientCapabilities": {},
-            "clientInfo": { "name": "kiro-notebook", "version": "0.1.0" }
-        }))
+        let response = self.send_request(&InitializeRequest {
+            protocol_version: PROTOCOL_VERSION,
+            client_capabilities: ClientCapabilities {
+                fs: Some(FileSystemCapability { read_text_file: true, write_text_file: true }),
+                terminal: false,
+            },
+            client_info,
+        }, self.timeouts.initialize_secs).await?;
+        if let Ok(mut agent) = self.agent.lock() {
+            *agent = Some(response.clone());
+        }
+        Ok(response)
+    }
+
+    /// What the agent reported it supports; all off until `initialize` has run
+    pub fn agent_capabilities(&self) -> AgentCapabilities {
+        self.agent.lock().ok()
+            .and_then(|agent| agent.as_ref().map(|a| a.agent_capabilities.clone()))
+            .unwrap_or_default()
     }
 
-    pub fn new_session(&mut self, cwd: &str) -> Result<String, String> {
+    pub async fn new_session(&mut self, cwd: &str) -> Result<String, AppError> {
         log(&format!("Creating session, cwd={}", cwd));
-        let result = self.send_request("session/new", json!({ "cwd": cwd, "mcpServers": [] }))?;
-        let session_id = result.get("sessionId").and_then(|v| v.as_str())
-            .ok_or("No sessionId in response")?.to_string();
+        let response = self.send_request(&NewSessionRequest {
+            cwd: cwd.to_string(),
+            mcp_servers: mcp_servers(cwd),
+        }, self.timeouts.new_session_secs).await?;
+        let session_id = response.session_id;
         log(&format!("Session created: {}", session_id));
+        self.set_capabilities(SessionCapabilities { models: response.models, modes: response.modes });
         self.session_id = Some(session_id.clone());
         Ok(session_id)
     }
 
-    pub fn load_session(&mut self, session_id: &str, cwd: &str) -> Result<(), String> {
+    pub async fn load_session(&mut self, session_id: &str, cwd: &str) -> Result<(), AppError> {
         log(&format!("Loading session: {}", session_id));
-        let _result = self.send_request("session/load", json!({
-            "sessionId": session_id,
-            "cwd": cwd,
-            "mcpServers": []
-        }))?;
+        let response = self.send_request(&LoadSessionRequest {
+            session_id: session_id.to_string(),
+            cwd: cwd.to_string(),
+            mcp_servers: mcp_servers(cwd),
+        }, self.timeouts.load_session_secs).await.map_err(|e| match e {
+            AppError::ProtocolError { code: RpcError::RESOURCE_NOT_FOUND, .. } => {
+                AppError::SessionNotFound { session_id: session_id.to_string() }
+            }
+            e => e,
+        })?;
+        self.set_capabilities(SessionCapabilities { models: response.models, modes: response.modes });
         self.session_id = Some(session_id.to_string());
         log(&format!("Session loaded: {}", session_id));
         Ok(())
     }
 
-    pub fn set_model(&mut self, model_id: &str) -> Result<(), String> {
-        let session_id = self.session_id.as_ref().ok_or("No active session")?.clone();
+    pub async fn set_model(&self, model_id: &str) -> Result<(), AppError> {
+        let session_id = self.session_id.clone().ok_or(AppError::NoActiveSession)?;
         log(&format!("Setting model: {}", model_id));
-        self.send_request("session/set_model", json!({
-            "sessionId": session_id,
-            "modelId": model_id
-        }))?;
+        self.send_request(&SetSessionModelRequest { session_id, model_id: model_id.to_string() }, self.timeouts.set_model_secs).await?;
+        if let Ok(mut capabilities) = self.capabilities.lock() {
+            if let Some(models) = capabilities.models.as_mut() {
+                models.current_model_id = model_id.to_string();
+            }
+        }
         Ok(())
     }
 
-    pub fn prompt_streaming(&mut self, text: &str, session_id: &str, app: &AppHandle) -> Result<String, String> {
+    pub async fn set_mode(&self, mode_id: &str) -> Result<(), AppError> {
+        let session_id = self.session_id.clone().ok_or(AppError::NoActiveSession)?;
+        log(&format!("Setting mode: {}", mode_id));
+        self.send_request(&SetSessionModeRequest { session_id, mode_id: mode_id.to_string() }, self.timeouts.set_model_secs).await?;
+        self.set_current_mode(mode_id);
+        Ok(())
+    }
+
+    pub fn capabilities(&self) -> SessionCapabilities {
+        self.capabilities.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    fn set_capabilities(&self, capabilities: SessionCapabilities) {
+        if let Ok(mut current) = self.capabilities.lock() {
+            *current = capabilities;
+        }
+    }
+
+    fn set_current_mode(&self, mode_id: &str) {
+        if let Ok(mut capabilities) = self.capabilities.lock() {
+            if let Some(modes) = capabilities.modes.as_mut() {
+                modes.current_mode_id = mode_id.to_string();
+            }
+        }
+    }
+
+    pub async fn prompt_streaming(
+        &self,
+        prompt: Vec<ContentBlock>,
+        session_id: &str,
+        policies: &HashMap<String, PermissionPolicy>,
+        events: &dyn EventSink,
+    ) -> Result<PromptOutcome, AppError> {
         let id = self.next_id();
-        log(&format!(">>> [id={}] prompt: {}...", id, &text[..text.len().min(80)]));
-        self.write_request(id, "session/prompt", json!({
-            "sessionId": session_id,
-            "prompt": [{ "type": "text", "text": text }]
-        }))?;
+        {
+            let mut turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+            if turns.contains_key(session_id) {
+                return Err(AppError::PromptRunning { session_id: session_id.to_string() });
+            }
+            turns.insert(session_id.to_string(), Turn { request_id: id, cancel_requested: false });
+        }
+        let started = std::time::Instant::now();
+        let result = self.run_turn(id, prompt, session_id, policies, events).await;
+        let cancel_requested = self.turns.lock().ok()
+            .and_then(|mut turns| turns.remove(session_id))
+            .map(|turn| turn.cancel_requested)
+            .unwrap_or(false);
 
-        let mut full_response = String::new();
+        let (result, progress) = result?;
+        let (stop_reason, response) = match result {
+            Ok(value) => {
+                let response: PromptResponse = serde_json::from_value(value)
+                    .map_err(|e| AppError::InvalidResponse { method: PromptRequest::METHOD.to_string(), message: e.to_string() })?;
+                (Some(response.stop_reason), Some(response))
+            }
+            // Agents may answer a cancelled prompt with an error instead of `cancelled`
+            Err(_) if cancel_requested && !self.is_closed() => (Some(StopReason::Cancelled), None),
+            Err(_) if self.is_closed() && !progress.text.is_empty() => (None, None),
+            Err(e) => return Err(e),
+        };
+        let (usage, meta) = response.map(|r| (r.usage, r.meta)).unwrap_or_default();
+        if let Some(stop_reason) = stop_reason {
+            log(&format!("<<< [id={}] turn ended: {:?}", id, stop_reason));
+            events.emit_event("acp-done", json!({ "sessionId": session_id, "stopReason": stop_reason }));
+        }
+        Ok(PromptOutcome {
+            text: progress.text,
+            stop_reason,
+            usage,
+            meta,
+            duration_ms: started.elapsed().as_millis() as u64,
+            tool_call_count: progress.tool_call_count,
+            chunk_count: progress.chunk_count,
+        })
+    }
 
-        loop {
-            match self.stdout_lines.next() {
-                Some(Ok(line)) => {
-                    if line.trim().is_empty() { continue; }
-                    let Ok(msg) = serde_json::from_str::<Value>(&line) else { continue };
-
-                    if let Some(msg_id) = msg.get("id").and_then(|v| v.as_u64()) {
-                        if msg_id == id {
-                            let _ = app.emit("acp-done", json!({ "sessionId": session_id }));
-                            return Ok(full_response);
-                        }
-                    }
+    /// Sends the prompt and streams updates until its response arrives. Returns the raw
+    /// response alongside what was streamed, which is kept even when the turn fails.
+    async fn run_turn(
+        &self,
+        id: u64,
+        prompt: Vec<ContentBlock>,
+        session_id: &str,
+        policies: &HashMap<String, PermissionPolicy>,
+        events: &dyn EventSink,
+    ) -> Result<(Result<Value, AppError>, TurnProgress), AppError> {
+        let mut updates = self.subscribe(session_id)?;
+        let mut response = match self.register(id) {
+            Ok(rx) => rx,
+            Err(e) => {
+                self.unsubscribe(session_id);
+                return Err(e);
+            }
+        };
+        let text = prompt.iter().find_map(ContentBlock::as_text).unwrap_or("");
+        log(&format!(">>> [id={}] prompt ({} blocks): {}...", id, prompt.len(), text.chars().take(80).collect::<String>()));
+        let request = PromptRequest { session_id: session_id.to_string(), prompt };
+        if let Err(e) = self.write_request(id, PromptRequest::METHOD, &request).await {
+            self.unregister(id);
+            self.unsubscribe(session_id);
+            return Err(e);
+        }
 
-                    if msg.get("method").and_then(|v| v.as_str()) == Some("session/update") {
-                        if let Some(update) = msg.pointer("/params/update") {
-                            let update_type = update.get("sessionUpdate").and_then(|v| v.as_str()).unwrap_or("");
-                            match update_type {
-                                "agent_message_chunk" => {
-                                    if let Some(text) = update.pointer("/content/text").and_then(|v| v.as_str()) {
-                                        full_response.push_str(text);
-                                        let _ = app.emit("acp-chunk", json!({ "sessionId": session_id, "text": text }));
-                                    }
-                                }
-                                "tool_call" => {
-                                    let title = update.get("title").and_then(|v| v.as_str()).unwrap_or("Working");
-                                    let status = update.get("status").and_then(|v| v.as_str()).unwrap_or("pending");
-                                    let _ = app.emit("acp-status", json!({ "sessionId": session_id, "type": "tool_call", "title": title, "status": status }));
-                                }
-                                "tool_call_update" => {
-                                    let status = update.get("status").and_then(|v| v.as_str()).unwrap_or("");
-                                    let _ = app.emit("acp-status", json!({ "sessionId": session_id, "type": "tool_update", "status": status }));
-                                }
-                                _ => {}
-                            }
+        let mut progress = TurnProgress::default();
+        let inactivity = std::time::Duration::from_secs(self.timeouts.prompt_inactivity_secs);
+        let mut idle_since = std::time::Instant::now();
+
+        let result = loop {
+            tokio::select! {
+                biased;
+                Some(msg) = updates.recv() => {
+                    idle_since = std::time::Instant::now();
+                    if msg.id.is_some() {
+                        self.handle_agent_request(msg, session_id, policies, &progress.tool_kinds, events).await;
+                    } else {
+                        self.handle_update(&msg, session_id, events, &mut progress);
+                    }
+                }
+                result = &mut response => {
+                    // Notifications routed just ahead of the response still belong to this turn
+                    while let Ok(msg) = updates.try_recv() {
+                        if msg.id.is_none() {
+                            self.handle_update(&msg, session_id, events, &mut progress);
                         }
                     }
+                    break result.unwrap_or(Err(AppError::ProcessClosed));
+                }
+                // Re-armed by every message, so this fires after `inactivity` of silence
+                _ = tokio::time::sleep(inactivity), if !inactivity.is_zero() => {
+                    if self.has_open_permissions() {
+                        // Waiting on the user, not on the agent
+                        idle_since = std::time::Instant::now();
+                        continue;
+                    }
+                    let idle_seconds = idle_since.elapsed().as_secs();
+                    log(&format!("<<< [id={}] no update for {}s", id, idle_seconds));
+                    events.emit_event("acp-stalled", serde_json::to_value(crate::events::StalledEvent {
+                        session_id: session_id.to_string(),
+                        idle_seconds,
+                    }).unwrap_or_default());
+                }
+            }
+        };
+        self.unsubscribe(session_id);
+        self.cancel_open_permissions().await;
+        Ok((result, progress))
+    }
+
+    /// Tracks turn progress from a `session/update` and forwards it to the UI as a typed event.
+    fn handle_update(
+        &self,
+        msg: &IncomingMessage,
+        session_id: &str,
+        events: &dyn EventSink,
+        progress: &mut TurnProgress,
+    ) {
+        if msg.method.as_deref() != Some(SESSION_UPDATE) { return; }
+        let notification: SessionNotification = match msg.parse_params() {
+            Ok(notification) => notification,
+            Err(e) => {
+                log(&format!("<<< {}: {}", SESSION_UPDATE, e));
+                return;
+            }
+        };
+        match &notification.update {
+            SessionUpdate::AgentMessageChunk { content } => {
+                if let Some(text) = content.as_text() {
+                    progress.text.push_str(text);
+                    progress.chunk_count += 1;
+                }
+            }
+            SessionUpdate::ToolCall(call) => {
+                progress.tool_kinds.insert(call.tool_call_id.clone(), call.kind);
+                progress.tool_call_count += 1;
+            }
+            SessionUpdate::CurrentModeUpdate { current_mode_id } => self.set_current_mode(current_mode_id),
+            _ => {}
+        }
+        if let Some((event, payload)) = crate::events::from_update(session_id, &notification.update) {
+            events.emit_event(event, payload);
+        }
+    }
+
+    async fn handle_agent_request(
+        &self,
+        msg: IncomingMessage,
+        session_id: &str,
+        policies: &HashMap<String, PermissionPolicy>,
+        tool_kinds: &HashMap<String, ToolKind>,
+        events: &dyn EventSink,
+    ) {
+        let Some(id) = msg.id.clone() else { return };
+        let method = msg.method.as_deref().unwrap_or("");
+        if method != REQUEST_PERMISSION {
+            let _ = write_message(&self.stdin, &method_not_found(&id, method)).await;
+            return;
+        }
+        let params: RequestPermissionRequest = match msg.parse_params() {
+            Ok(params) => params,
+            Err(error) => {
+                let _ = write_message(&self.stdin, &Response::<Value>::error(id, error)).await;
+                return;
+            }
+        };
+
+        let kind = params.tool_call.kind
+            .or_else(|| tool_kinds.get(&params.tool_call.tool_call_id).copied())
+            .unwrap_or_default();
+        let policy = policies.get(kind.as_str()).copied().unwrap_or(PermissionPolicy::Ask);
+        let auto_choice = match policy {
+            PermissionPolicy::Ask => None,
+            PermissionPolicy::AutoAllow => pick_option(&params.options, &[PermissionOptionKind::AllowOnce, PermissionOptionKind::AllowAlways]),
+            PermissionPolicy::AutoDeny => pick_option(&params.options, &[PermissionOptionKind::RejectOnce, PermissionOptionKind::RejectAlways]),
+        };
+        if let Some(option_id) = auto_choice {
+            log(&format!("Permission for {} ({:?}): {}", kind.as_str(), policy, option_id));
+            let _ = write_message(&self.stdin, &permission_response(&id, Some(&option_id))).await;
+            return;
+        }
+
+        if let Ok(mut open) = self.open_permissions.lock() {
+            open.insert(id.clone());
+        }
+        events.emit_event("acp-permission", json!({
+            "sessionId": session_id,
+            "requestId": id,
+            "kind": kind,
+            "toolCall": params.tool_call,
+            "options": params.options,
+        }));
+    }
+
+    /// Answer a permission request the UI was asked about. `None` cancels it.
+    pub async fn respond_permission(&self, request_id: &RequestId, option_id: Option<&str>) -> Result<(), AppError> {
+        let was_open = self.open_permissions.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(request_id);
+        if !was_open {
+            return Err(AppError::PermissionNotPending);
+        }
+        log(&format!(">>> [id={}] permission: {}", request_id, option_id.unwrap_or("cancelled")));
+        write_message(&self.stdin, &permission_response(request_id, option_id)).await
+    }
+
+    fn has_open_permissions(&self) -> bool {
+        self.open_permissions.lock().map(|open| !open.is_empty()).unwrap_or(false)
+    }
+
+    /// ACP requires every outstanding permission request to be answered once the turn is over
+    async fn cancel_open_permissions(&self) {
+        let open: Vec<RequestId> = match self.open_permissions.lock() {
+            Ok(mut open) => open.drain().collect(),
+            Err(_) => return,
+        };
+        for id in open {
+            let _ = write_message(&self.stdin, &permission_response(&id, None)).await;
+        }
+    }
+
+    /// Ask the agent to stop the prompt running in `session_id`. `session/cancel` is a
+    /// notification; the prompt itself then ends with `stopReason: cancelled`.
+    /// Returns `false` when there was nothing to cancel.
+    pub async fn cancel(&self, session_id: &str) -> Result<bool, AppError> {
+        let request_id = {
+            let mut turns = self.turns.lock().unwrap_or_else(PoisonError::into_inner);
+            match turns.get_mut(session_id) {
+                Some(turn) => {
+                    turn.cancel_requested = true;
+                    turn.request_id
                 }
-                Some(Err(e)) => return Err(format!("Read error: {}", e)),
-                None => {
-                    if !full_response.is_empty() { return Ok(full_response); }
-                    return Err("ACP process closed".to_string());
+                None => return Ok(false),
+            }
+        };
+        log(&format!(">>> session/cancel (prompt id={})", request_id));
+        write_message(&self.stdin, &Notification::new(CancelNotification::METHOD, CancelNotification {
+            session_id: session_id.to_string(),
+        })).await?;
+        self.cancel_open_permissions().await;
+        Ok(true)
+    }
+
+    /// Whether a prompt is running; such a process must not be stopped to save resources.
+    pub fn is_busy(&self) -> bool {
+        self.turns.lock().map(|turns| !turns.is_empty()).unwrap_or(true)
+    }
+
+    /// Whether the agent's stdout has closed; no request can succeed after that.
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Exit status of the agent process, `None` while it is running.
+    pub fn exit_status(&self) -> Option<String> {
+        let mut child = self.child.lock().ok()?;
+        child.as_mut()?.try_wait().ok().flatten().map(|status| status.to_string())
+    }
+
+    /// The last lines the agent wrote to stderr, oldest first.
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().map(|tail| tail.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Where to look when a session misbehaves.
+    pub fn diagnostics(&self) -> Diagnostics {
+        let exit_status = self.exit_status();
+        Diagnostics {
+            pid: self.pid,
+            running: exit_status.is_none() && !self.is_closed(),
+            exit_status,
+            stderr: self.stderr_tail(),
+            trace_path: self.trace.as_ref().map(|t| t.path().display().to_string()),
+            agent_info: self.agent.lock().ok().and_then(|agent| agent.as_ref()?.agent_info.clone()),
+        }
+    }
+
+    /// Resolves once the agent is gone: its stdout closed or the process exited, which
+    /// also catches an exit while a child process still holds stdout open.
+    /// Returns `false` when it went away through `kill`, `true` when it crashed.
+    pub async fn wait_exit(&self) -> bool {
+        let mut closed = self.closed.clone();
+        loop {
+            if self.killed.load(Ordering::SeqCst) {
+                return false;
+            }
+            if *closed.borrow() || self.exit_status().is_some() {
+                return true;
+            }
+            tokio::select! {
+                changed = closed.changed() => {
+                    // The reader only goes away without closing when `kill` aborted it
+                    if changed.is_err() {
+                        return !self.killed.load(Ordering::SeqCst) && *closed.borrow();
+                    }
                 }
+                _ = tokio::time::sleep(EXIT_POLL_INTERVAL) => {}
             }
         }
     }
 
-    pub fn kill(&mut self) {
-        let pid = self.child.id();
+    /// Stop the agent: SIGTERM to its process group now, and from a background task
+    /// SIGKILL once `KILL_GRACE` is up, then reap it. Returns right away, so it is fine
+    /// to call from async code.
+    pub fn kill(&self) {
+        let Some(mut child) = self.stop() else { return };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            // Nothing to wait on without a runtime; tokio reaps the dropped child
+            kill_now(&mut child);
+            return;
+        };
         #[cfg(unix)]
-        {
-            unsafe { libc::kill(-(pid as i32), libc::SIGTERM); }
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            unsafe { libc::kill(-(pid as i32), libc::SIGKILL); }
+        if let Some(pid) = child.id() {
+            signal_group(pid, libc::SIGTERM);
         }
-        #[cfg(not(unix))]
-        {
-            let _ = self.child.kill();
+        runtime.spawn(async move {
+            tokio::time::sleep(KILL_GRACE).await;
+            kill_now(&mut child);
+            let _ = child.wait().await;
+        });
+    }
+
+    /// Mark the client killed and stop its readers; the child when this was the first time.
+    fn stop(&self) -> Option<Child> {
+        if self.killed.swap(true, Ordering::SeqCst) {
+            return None;
         }
-        let _ = self.child.wait();
+        self.reader.abort();
+        self.stderr_reader.abort();
+        self.child.lock().ok()?.take()
     }
 }
 
 impl Drop for AcpClient {
     fn drop(&mut self) {
-        self.kill();
-    }
-}
-
-/// Send cancel to a session's stdin without needing exclusive access to AcpClient
-pub fn send_cancel(stdin: &SharedStdin, session_id: &str, request_id: u64) -> Result<(), String> {
-    let request = json!({
-        "jsonrpc": "2.0",
-        "id": request_id,
-        "method": "session/cancel",
-        "params": { "sessionId": session_id }
-    });
-    let msg = serde_json::to_string(&request).unwrap();
-    log(&format!(">>> [id={}] session/cancel", request_id));
-    let mut stdin = stdin.lock().map_err(|e| format!("Lock error: {}", e))?;
-    stdin.write_all(format!("{}\n", msg).as_bytes()).map_err(|e| format!("Write error: {}", e))?;
-    stdin.flush().map_err(|e| format!("Flush error: {}", e))
+        // Dropped while the pool or session maps are locked: no grace period, tokio
+        // reaps the child once it is gone
+        if let Some(mut child) = self.stop() {
+            kill_now(&mut child);
+        }
+    }
+}
+
+/// SIGKILL to the agent and anything it started.
+fn kill_now(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        signal_group(pid, libc::SIGKILL);
+    }
+    let _ = child.start_kill();
+}
+
+/// The agent runs in its own process group (see `spawn_command`), so its children
+/// get the signal too.
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    unsafe { libc::kill(-(pid as i32), signal); }
+}
+
+async fn write_message<T: Serialize>(out: &SharedStdin, msg: &T) -> Result<(), AppError> {
+    let value = serde_json::to_value(msg).map_err(AppError::internal)?;
+    let line = format!("{}\n", value);
+    let mut stdin = out.stdin.lock().await;
+    if let Some(trace) = &out.trace {
+        trace.record(Direction::Out, &value);
+    }
+    // The agent has exited when its stdin is gone
+    stdin.write_all(line.as_bytes()).await.map_err(|_| AppError::ProcessClosed)?;
+    stdin.flush().await.map_err(|_| AppError::ProcessClosed)
+}
+
+/// Reads stdout until EOF, handing responses to their pending request and
+/// notifications to the subscriber of their session.
+async fn read_loop(
+    stdout: ChildStdout,
+    stdin: SharedStdin,
+    pending: Pending,
+    subscribers: Subscribers,
+    closed: watch::Sender<bool>,
+    workspace: Option<PathBuf>,
+    trace: Option<Arc<Trace>>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                if line.trim().is_empty() { continue; }
+                let Ok(value) = serde_json::from_str::<Value>(&line) else {
+                    log(&format!("<<< unparsable: {}", line));
+                    continue;
+                };
+                if let Some(trace) = &trace {
+                    trace.record(Direction::In, &value);
+                }
+                let msg = match serde_json::from_value::<IncomingMessage>(value) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        log(&format!("<<< malformed message ({}): {}", e, line));
+                        continue;
+                    }
+                };
+                route_message(msg, &stdin, &pending, &subscribers, &workspace).await;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                log(&format!("Read error: {}", e));
+                break;
+            }
+        }
+    }
+
+    log("ACP process closed");
+    closed.send_replace(true);
+    if let Ok(mut pending) = pending.lock() {
+        for (_, tx) in pending.drain() {
+            let _ = tx.send(Err(AppError::ProcessClosed));
+        }
+    }
+    if let Ok(mut subscribers) = subscribers.lock() {
+        subscribers.clear();
+    }
+}
+
+/// Keeps the tail of the agent's stderr for crash reports, echoing it to ours under `label`.
+async fn read_stderr(stderr: ChildStderr, label: String, tail: StderrTail) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        eprintln!("[{}] {}", label, line);
+        if let Ok(mut tail) = tail.lock() {
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    }
+}
+
+async fn route_message(
+    msg: IncomingMessage,
+    stdin: &SharedStdin,
+    pending: &Pending,
+    subscribers: &Subscribers,
+    workspace: &Option<PathBuf>,
+) {
+    match (msg.method.clone(), msg.id.clone()) {
+        // Response to one of our requests
+        (None, Some(id)) => {
+            let RequestId::Number(id) = id else {
+                log(&format!("<<< response with unexpected id: {}", id));
+                return;
+            };
+            let tx = pending.lock().ok().and_then(|mut p| p.remove(&id));
+            let Some(tx) = tx else {
+                log(&format!("<<< [id={}] response with no pending request", id));
+                return;
+            };
+            let result = match msg.error {
+                Some(error) => Err(AppError::from(error)),
+                None => Ok(msg.result.unwrap_or(Value::Null)),
+            };
+            let _ = tx.send(result);
+        }
+        // File system requests are served directly, off the reader task
+        (Some(method), Some(id)) if method.starts_with("fs/") => {
+            log(&format!("<<< [id={}] {}", id, method));
+            let stdin = stdin.clone();
+            let workspace = workspace.clone();
+            tokio::spawn(async move {
+                let result = tokio::task::spawn_blocking(move || {
+                    client_fs::handle_request(&msg, workspace.as_deref())
+                })
+                .await
+                .unwrap_or_else(|e| Err(RpcError::new(RpcError::INTERNAL_ERROR, e.to_string())));
+                let _ = match result {
+                    Ok(result) => write_message(&stdin, &Response::result(id, result)).await,
+                    Err(error) => write_message(&stdin, &Response::<Value>::error(id, error)).await,
+                };
+            });
+        }
+        // Any other request from the agent is handled by the session's running prompt
+        (Some(method), Some(id)) => {
+            let session_id = msg.session_id().unwrap_or("").to_string();
+            let tx = subscribers.lock().ok().and_then(|s| s.get(&session_id).cloned());
+            if let Some(tx) = tx {
+                let _ = tx.send(msg);
+                return;
+            }
+            log(&format!("<<< [id={}] {} outside of a prompt turn", id, method));
+            let _ = if method == REQUEST_PERMISSION {
+                write_message(stdin, &permission_response(&id, None)).await
+            } else {
+                write_message(stdin, &method_not_found(&id, &method)).await
+            };
+        }
+        // Notification: deliver to whoever is listening on that session
+        (Some(method), None) => {
+            let session_id = msg.session_id().unwrap_or("").to_string();
+            let tx = subscribers.lock().ok().and_then(|s| s.get(&session_id).cloned());
+            match tx {
+                Some(tx) => { let _ = tx.send(msg); }
+                None => log(&format!("<<< {} for session '{}' with no subscriber", method, session_id)),
+            }
+        }
+        (None, None) => log(&format!("<<< malformed message: {:?}", msg)),
+    }
+}
+
+fn method_not_found(id: &RequestId, method: &str) -> Response<Value> {
+    Response::error(id.clone(), RpcError::method_not_found(method))
+}
+
+fn permission_response(id: &RequestId, option_id: Option<&str>) -> Response<RequestPermissionResponse> {
+    let outcome = match option_id {
+        Some(option_id) => RequestPermissionOutcome::Selected { option_id: option_id.to_string() },
+        None => RequestPermissionOutcome::Cancelled,
+    };
+    Response::result(id.clone(), RequestPermissionResponse { outcome })
+}
+
+/// First option whose `kind` matches, in order of preference
+fn pick_option(options: &[PermissionOption], kinds: &[PermissionOptionKind]) -> Option<String> {
+    kinds.iter().find_map(|kind| {
+        options.iter()
+            .find(|o| o.kind == *kind)
+            .map(|o| o.option_id.clone())
+    })
 }
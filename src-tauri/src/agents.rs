@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::acp::AgentCommand;
+use crate::error::AppError;
+use crate::protocol::Implementation;
+use crate::settings::{KiroCliSettings, Settings};
+
+/// Id of the built-in Kiro profile, used when a session does not name one.
+pub const KIRO: &str = "kiro";
+
+/// An ACP-speaking agent a chat tab can run: a named command line plus the
+/// agent-specific behaviour the generic client cannot infer.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AgentProfile {
+    pub id: String,
+    pub name: String,
+    /// Program to run; may be empty when `quirks.find_kiro_cli` is set
+    pub command: String,
+    pub args: Vec<String>,
+    /// Added to the app's environment
+    pub env: HashMap<String, String>,
+    /// Directory the process starts in; the workspace when unset
+    pub working_dir: Option<String>,
+    pub quirks: Quirks,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Quirks {
+    /// Without a `command`, look for `kiro-cli` in its usual install locations
+    pub find_kiro_cli: bool,
+    /// Kiro locks sessions under `~/.kiro/sessions/cli` and refuses to load one whose
+    /// lock a dead process left behind; remove that lock and retry `session/load`
+    pub kiro_session_locks: bool,
+    /// Sent as `clientInfo` with `initialize`
+    pub client_info: Option<Implementation>,
+}
+
+impl AgentProfile {
+    /// The built-in profile: `kiro-cli acp`, adjusted by the user's kiro-cli settings.
+    pub fn kiro(settings: &KiroCliSettings) -> Self {
+        let mut args = vec!["acp".to_string()];
+        args.extend(settings.args.iter().cloned());
+        Self {
+            id: KIRO.to_string(),
+            name: "Kiro CLI".to_string(),
+            command: settings.path.clone().unwrap_or_default(),
+            args,
+            env: settings.env.clone(),
+            working_dir: settings.working_dir.clone(),
+            quirks: Quirks {
+                find_kiro_cli: true,
+                kiro_session_locks: true,
+                client_info: Some(Implementation {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    title: None,
+                }),
+            },
+        }
+    }
+
+    /// The command line to spawn, with the binary located if the profile needs that.
+    pub fn command(&self) -> Result<AgentCommand, AppError> {
+        let program = if self.quirks.find_kiro_cli {
+            find_kiro_cli(Some(self.command.as_str()))?
+        } else if self.command.is_empty() {
+            return Err(AppError::SpawnFailed { program: self.name.clone(), message: "no command configured".to_string() });
+        } else {
+            self.command.clone()
+        };
+        Ok(AgentCommand {
+            program,
+            args: self.args.clone(),
+            env: self.env.clone(),
+            working_dir: self.working_dir.clone().filter(|dir| !dir.is_empty()),
+        })
+    }
+
+    /// `error` from a failed `session/load`, as `SessionLocked` when it is the profile's
+    /// lock conflict, with the owner its lock names.
+    pub fn load_error(&self, error: AppError, session_id: &str) -> AppError {
+        match error {
+            // Kiro says so only in the message, under a generic error code
+            AppError::ProtocolError { ref message, .. }
+                if self.quirks.kiro_session_locks && message.contains("active in another process") =>
+            {
+                let lock = locks_dir().and_then(|dir| read_lock(&dir, session_id).ok().flatten());
+                AppError::SessionLocked {
+                    session_id: session_id.to_string(),
+                    pid: lock.as_ref().map(|lock| lock.pid),
+                    started_at: lock.and_then(|lock| lock.started_at),
+                }
+            }
+            error => error,
+        }
+    }
+
+    /// Remove the lock on `session_id` if the process it names is gone. `true` when
+    /// loading again may now work, `false` when a live (or unknown) owner still holds it.
+    pub fn release_stale_lock(&self, session_id: &str) -> Result<bool, AppError> {
+        release_stale_lock_in(&locks_dir().ok_or_else(no_home)?, session_id)
+    }
+
+    /// Remove the lock on `session_id` whoever holds it; the user chose to take the session over.
+    pub fn release_lock(&self, session_id: &str) -> Result<(), AppError> {
+        let path = lock_path(&locks_dir().ok_or_else(no_home)?, session_id)?;
+        eprintln!("[AGENTS] Taking over lock: {}", path.display());
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::io(&path, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What Kiro writes to `~/.kiro/sessions/cli/<session id>.lock`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionLock {
+    pub pid: u32,
+    #[serde(default)]
+    pub started_at: Option<String>,
+}
+
+fn no_home() -> AppError {
+    AppError::NoUserDirectory { name: "home".to_string() }
+}
+
+fn locks_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".kiro/sessions/cli"))
+}
+
+/// `<dir>/<session id>.lock`. The id comes from the UI, so anything but a plain file
+/// name (letters, digits, `-` and `_`) is refused rather than joined into a path.
+fn lock_path(dir: &Path, session_id: &str) -> Result<PathBuf, AppError> {
+    let plain = !session_id.is_empty()
+        && session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !plain {
+        return Err(AppError::InvalidSessionId { session_id: session_id.to_string() });
+    }
+    Ok(dir.join(format!("{}.lock", session_id)))
+}
+
+/// The lock on `session_id`; `None` when there is none.
+fn read_lock(dir: &Path, session_id: &str) -> Result<Option<SessionLock>, AppError> {
+    let path = lock_path(dir, session_id)?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(AppError::io(&path, e)),
+    };
+    serde_json::from_str(&content).map(Some).map_err(|e| AppError::io(&path, e))
+}
+
+fn release_stale_lock_in(dir: &Path, session_id: &str) -> Result<bool, AppError> {
+    let path = lock_path(dir, session_id)?;
+    let lock = match read_lock(dir, session_id) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => return Ok(true),
+        // Without a pid there is no telling whether the owner is gone
+        Err(e) => {
+            eprintln!("[AGENTS] {}", e);
+            return Ok(false);
+        }
+    };
+    if is_alive(lock.pid) {
+        return Ok(false);
+    }
+    eprintln!("[AGENTS] Removing stale lock of dead PID {}: {}", lock.pid, path.display());
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::io(&path, e)),
+        _ => Ok(true),
+    }
+}
+
+/// Whether process `pid` exists. Signal 0 checks without sending anything; `EPERM`
+/// means it exists but belongs to another user.
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    // 0 and negative pids address process groups
+    let Ok(pid) = i32::try_from(pid) else { return false };
+    if pid <= 0 {
+        return false;
+    }
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// No cheap check here, so every owner counts as alive and no lock is removed unasked
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Kiro first, then the profiles the user added in the settings.
+pub fn profiles(settings: &Settings) -> Vec<AgentProfile> {
+    let mut profiles = vec![AgentProfile::kiro(&settings.kiro_cli)];
+    profiles.extend(settings.agents.iter().filter(|p| p.id != KIRO).cloned());
+    profiles
+}
+
+/// The profile called `id`, or the default one when `id` is `None`.
+pub fn find(settings: &Settings, id: Option<&str>) -> Result<AgentProfile, AppError> {
+    let id = id.or(settings.default_agent.as_deref()).unwrap_or(KIRO);
+    profiles(settings)
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| AppError::AgentNotFound { agent_id: id.to_string() })
+}
+
+/// `configured` when set, otherwise the usual install locations.
+fn find_kiro_cli(configured: Option<&str>) -> Result<String, AppError> {
+    if let Some(path) = configured.filter(|p| !p.is_empty()) {
+        return if Path::new(path).exists() {
+            Ok(path.to_string())
+        } else {
+            Err(AppError::CliNotFound { path: Some(path.to_string()) })
+        };
+    }
+
+    // Explicit override, e.g. to point at a different build or at the mock agent
+    if let Ok(path) = std::env::var("KIRO_CLI_PATH") {
+        if !path.is_empty() && Path::new(&path).exists() {
+            return Ok(path);
+        }
+    }
+
+    let candidates = [
+        "/Applications/Kiro CLI.app/Contents/MacOS/kiro-cli",
+        &format!("{}/.local/bin/kiro-cli", std::env::var("HOME").unwrap_or_default()),
+        "/usr/local/bin/kiro-cli",
+    ];
+
+    for path in &candidates {
+        if Path::new(path).exists() {
+            return Ok(path.to_string());
+        }
+    }
+
+    if let Ok(output) = std::process::Command::new("which").arg("kiro-cli").output() {
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !path.is_empty() && Path::new(&path).exists() {
+            return Ok(path);
+        }
+    }
+
+    Err(AppError::CliNotFound { path: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(id: &str) -> AgentProfile {
+        AgentProfile {
+            id: id.to_string(),
+            name: "Gemini".to_string(),
+            command: "gemini".to_string(),
+            args: vec!["--experimental-acp".to_string()],
+            ..AgentProfile::default()
+        }
+    }
+
+    #[test]
+    fn kiro_profile_applies_cli_settings() {
+        let settings = KiroCliSettings {
+            path: Some("/opt/kiro-cli".to_string()),
+            args: vec!["--trust-all-tools".to_string()],
+            env: HashMap::from([("KIRO_LOG_LEVEL".to_string(), "debug".to_string())]),
+            working_dir: Some(String::new()),
+        };
+        let kiro = AgentProfile::kiro(&settings);
+        assert_eq!(kiro.args, vec!["acp", "--trust-all-tools"]);
+        assert!(kiro.quirks.kiro_session_locks);
+        assert_eq!(kiro.command().unwrap_err(), AppError::CliNotFound { path: Some("/opt/kiro-cli".to_string()) });
+    }
+
+    #[test]
+    fn custom_profile_runs_its_command_as_is() {
+        let command = custom("gemini").command().unwrap();
+        assert_eq!(command.program, "gemini");
+        assert_eq!(command.args, vec!["--experimental-acp"]);
+        assert!(AgentProfile { command: String::new(), ..custom("gemini") }.command().is_err());
+    }
+
+    #[test]
+    fn only_kiro_reports_lock_conflicts() {
+        let conflict = || AppError::ProtocolError {
+            code: -32603,
+            message: "Session is active in another process".to_string(),
+            data: None,
+        };
+        let kiro = AgentProfile::kiro(&KiroCliSettings::default());
+        assert!(matches!(kiro.load_error(conflict(), "s1"), AppError::SessionLocked { session_id, .. } if session_id == "s1"));
+        assert_eq!(custom("gemini").load_error(conflict(), "s1"), conflict());
+        assert_eq!(kiro.load_error(AppError::ProcessClosed, "s1"), AppError::ProcessClosed);
+    }
+
+    #[test]
+    fn only_locks_of_dead_processes_are_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        let write = |id: &str, content: String| std::fs::write(lock_path(dir.path(), id).unwrap(), content).unwrap();
+        write("dead", format!(r#"{{"pid":{},"started_at":"2026-01-01T00:00:00Z"}}"#, exited.id()));
+        write("alive", format!(r#"{{"pid":{}}}"#, std::process::id()));
+        write("garbled", "not json".to_string());
+
+        assert_eq!(
+            read_lock(dir.path(), "alive").unwrap(),
+            Some(SessionLock { pid: std::process::id(), started_at: None }),
+        );
+        assert!(release_stale_lock_in(dir.path(), "dead").unwrap());
+        assert!(!lock_path(dir.path(), "dead").unwrap().exists());
+        assert!(!release_stale_lock_in(dir.path(), "alive").unwrap());
+        assert!(lock_path(dir.path(), "alive").unwrap().exists());
+        assert!(!release_stale_lock_in(dir.path(), "garbled").unwrap());
+        assert!(release_stale_lock_in(dir.path(), "missing").unwrap());
+    }
+
+    #[test]
+    fn session_ids_must_be_plain_file_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside.lock");
+        std::fs::write(&outside, "{}").unwrap();
+        let locks = dir.path().join("locks");
+        assert!(lock_path(&locks, "3f1c-9a_b2").is_ok());
+        for id in ["../outside", "a/b", "/etc/x", "..", "", "a.b"] {
+            let invalid = AppError::InvalidSessionId { session_id: id.to_string() };
+            assert_eq!(lock_path(&locks, id).unwrap_err(), invalid);
+            assert_eq!(read_lock(&locks, id).unwrap_err(), invalid);
+            assert_eq!(release_stale_lock_in(&locks, id).unwrap_err(), invalid);
+        }
+        assert!(outside.exists());
+    }
+
+    #[test]
+    fn find_falls_back_to_the_default_agent() {
+        let mut settings = Settings { agents: vec![custom("gemini"), custom(KIRO)], ..Settings::default() };
+        let ids: Vec<String> = profiles(&settings).into_iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![KIRO, "gemini"]);
+        assert_eq!(find(&settings, None).unwrap().id, KIRO);
+        settings.default_agent = Some("gemini".to_string());
+        assert_eq!(find(&settings, None).unwrap().id, "gemini");
+        assert_eq!(find(&settings, Some(KIRO)).unwrap().id, KIRO);
+        assert!(find(&settings, Some("missing")).is_err());
+    }
+}
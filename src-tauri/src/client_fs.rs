@@ -0,0 +1,139 @@
+use serde_json::Value;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::AppError;
+use crate::file_reader;
+use crate::protocol::{
+    IncomingMessage, ReadTextFileRequest, ReadTextFileResponse, RpcError, WriteTextFileRequest,
+    READ_TEXT_FILE, WRITE_TEXT_FILE,
+};
+use crate::workspace::resolve_in_workspace;
+
+/// Where `fs/write_text_file` may write, relative to the workspace root.
+pub const NOTES_DIR: &str = ".kiro-notebook/notes";
+
+/// Serve an agent `fs/*` request. Both are limited to `workspace`, the session's
+/// canonical working directory.
+pub fn handle_request(msg: &IncomingMessage, workspace: Option<&Path>) -> Result<Value, RpcError> {
+    match msg.method.as_deref().unwrap_or("") {
+        READ_TEXT_FILE => {
+            let workspace = workspace.ok_or_else(|| rpc_error(AppError::NoWorkspace))?;
+            let response = read_text_file(&msg.parse_params()?, workspace)?;
+            serde_json::to_value(response).map_err(|e| rpc_error(AppError::internal(e)))
+        }
+        WRITE_TEXT_FILE => {
+            let workspace = workspace.ok_or_else(|| rpc_error(AppError::NoWorkspace))?;
+            write_text_file(&msg.parse_params()?, workspace)
+        }
+        method => Err(RpcError::method_not_found(method)),
+    }
+}
+
+/// The agent gets the structured error as `data`; paths it may not use are its mistake.
+fn rpc_error(error: AppError) -> RpcError {
+    let code = match error {
+        AppError::AccessDenied { .. } | AppError::OutsideNotes { .. } => RpcError::INVALID_PARAMS,
+        _ => RpcError::INTERNAL_ERROR,
+    };
+    RpcError { data: serde_json::to_value(&error).ok(), ..RpcError::new(code, error.to_string()) }
+}
+
+fn absolute_path(path: &str) -> Result<PathBuf, RpcError> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err(RpcError::new(RpcError::INVALID_PARAMS, format!("Path must be absolute: {}", path.display())));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Documents go through `file_reader` so PDF and DOCX come back as extracted text;
+/// anything else is read as plain UTF-8. Only files in the workspace are served.
+fn read_text_file(params: &ReadTextFileRequest, workspace: &Path) -> Result<ReadTextFileResponse, RpcError> {
+    let path = absolute_path(&params.path)?;
+    let path = resolve_in_workspace(workspace, &path.to_string_lossy()).map_err(rpc_error)?;
+    let content = if file_reader::needs_extraction(&path) {
+        file_reader::read_file(&path.to_string_lossy())
+    } else {
+        fs::read_to_string(&path).map_err(|e| AppError::ExtractionFailed { path: path.display().to_string(), message: e.to_string() })
+    };
+    let content = content.map_err(rpc_error)?;
+
+    // `line` is 1-based; `limit` caps the number of lines returned
+    let line = params.line.unwrap_or(1).max(1) as usize;
+    let limit = params.limit.map(|l| l as usize);
+    let content = if line > 1 || limit.is_some() {
+        let lines = content.lines().skip(line - 1);
+        match limit {
+            Some(limit) => lines.take(limit).collect::<Vec<_>>().join("\n"),
+            None => lines.collect::<Vec<_>>().join("\n"),
+        }
+    } else {
+        content
+    };
+
+    Ok(ReadTextFileResponse { content })
+}
+
+fn write_text_file(params: &WriteTextFileRequest, workspace: &Path) -> Result<Value, RpcError> {
+    let path = absolute_path(&params.path)?;
+    let path = resolve_note_path(workspace, &path).map_err(rpc_error)?;
+    fs::write(&path, &params.content).map_err(|e| rpc_error(AppError::io(&path, e)))?;
+    Ok(Value::Null)
+}
+
+/// Checks that `path` lies inside the notes area, creating its parent directories.
+/// Symlinks are resolved so they cannot lead outside of it, and nothing is created
+/// before the existing part of the path has been checked.
+fn resolve_note_path(workspace: &Path, path: &Path) -> Result<PathBuf, AppError> {
+    let notes = workspace.join(NOTES_DIR);
+    let denied = || AppError::OutsideNotes { path: path.display().to_string(), notes: notes.display().to_string() };
+
+    if path.components().any(|c| matches!(c, Component::ParentDir)) || !path.starts_with(&notes) {
+        return Err(denied());
+    }
+    let parent = path.parent().ok_or_else(denied)?;
+    let file_name = path.file_name().ok_or_else(denied)?;
+    fs::create_dir_all(&notes).map_err(|e| AppError::io(&notes, e))?;
+    let notes = fs::canonicalize(&notes).map_err(|e| AppError::io(&notes, e))?;
+
+    // The deepest directory that exists decides where the missing ones would go
+    let existing = parent.ancestors().find(|dir| dir.exists()).ok_or_else(denied)?;
+    let existing = fs::canonicalize(existing).map_err(|e| AppError::io(existing, e))?;
+    if !existing.starts_with(&notes) {
+        return Err(denied());
+    }
+    fs::create_dir_all(parent).map_err(|e| AppError::io(parent, e))?;
+
+    let resolved = match fs::canonicalize(path) {
+        Ok(existing) => existing,
+        Err(_) => fs::canonicalize(parent).map_err(|e| AppError::io(parent, e))?.join(file_name),
+    };
+    if !resolved.starts_with(&notes) {
+        return Err(denied());
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_notes_get_nothing_created() {
+        let workspace = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let notes = workspace.path().join(NOTES_DIR);
+        fs::create_dir_all(&notes).unwrap();
+        std::os::unix::fs::symlink(outside.path(), notes.join("link")).unwrap();
+
+        let err = resolve_note_path(workspace.path(), &notes.join("link/new/dir/note.md")).unwrap_err();
+        assert!(matches!(err, AppError::OutsideNotes { .. }), "{:?}", err);
+        assert!(!outside.path().join("new").exists());
+
+        let inside = resolve_note_path(workspace.path(), &notes.join("2026/october/note.md")).unwrap();
+        assert!(inside.parent().unwrap().is_dir());
+        assert!(resolve_note_path(workspace.path(), &notes.join("../escape.md")).is_err());
+    }
+}
@@ -1,9 +1,17 @@
-use crate::acp::{self, AcpClient};
+use crate::acp::{AcpClient, Diagnostics, PermissionPolicy, PromptOutcome, SessionCapabilities, Timeouts};
+use crate::agents::{self, AgentProfile};
+use crate::error::AppError;
 use crate::file_reader;
+use crate::pool::{self, SessionMeta};
+use crate::prompt::{self, PromptPart};
+use crate::protocol::{AgentCapabilities, Implementation, RequestId};
+use crate::settings::{self, KiroCliSettings, Settings};
+use crate::supervisor;
 use crate::AppState;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Instant;
 use walkdir::WalkDir;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -19,22 +27,25 @@ pub struct FileEntry {
 pub async fn select_workspace(
     path: String,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let canonical = std::fs::canonicalize(&path)
-        .map_err(|e| format!("Invalid path: {}", e))?
+        .map_err(|e| AppError::from_io(std::path::Path::new(&path), e))?
         .display()
         .to_string();
     *state.workspace.lock().await = Some(canonical.clone());
+    // Warm processes belong to the workspace they were started in
+    state.pool.clear().await;
+    refill_pool(&canonical, &state).await;
     Ok(canonical)
 }
 
 #[tauri::command]
 pub async fn list_files(
     state: tauri::State<'_, AppState>,
-) -> Result<Vec<FileEntry>, String> {
+) -> Result<Vec<FileEntry>, AppError> {
     let workspace = state.workspace.lock().await;
-    let workspace = workspace.as_ref().ok_or("No workspace selected")?;
-    let extensions = ["pdf", "docx", "md", "txt", "html"];
+    let workspace = workspace.as_ref().ok_or(AppError::NoWorkspace)?;
+    let extensions = ["pdf", "docx", "md", "txt", "html", "htm", "xhtml", "epub", "png", "jpg", "jpeg"];
     let mut entries: Vec<FileEntry> = Vec::new();
 
     for entry in WalkDir::new(workspace)
@@ -66,155 +77,430 @@ pub async fn list_files(
 }
 
 #[tauri::command]
-pub async fn read_file_content(path: String) -> Result<String, String> {
+pub async fn read_file_content(path: String) -> Result<String, AppError> {
     file_reader::read_file(&path)
 }
 
-fn get_cwd(workspace: &tokio::sync::MutexGuard<'_, Option<String>>) -> Result<String, String> {
-    workspace.as_ref().ok_or("No workspace selected".to_string()).cloned()
+fn not_found(session_id: &str) -> AppError {
+    AppError::SessionNotFound { session_id: session_id.to_string() }
 }
 
-async fn spawn_and_init(_cwd: &str) -> Result<AcpClient, String> {
-    let kiro_path = find_kiro_cli()?;
-    let mut client = AcpClient::spawn(&kiro_path)?;
-    client.initialize()?;
+fn get_cwd(workspace: &tokio::sync::MutexGuard<'_, Option<String>>) -> Result<String, AppError> {
+    workspace.as_ref().ok_or(AppError::NoWorkspace).cloned()
+}
+
+pub(crate) async fn spawn_and_init(cwd: &str, profile: &AgentProfile, timeouts: Timeouts) -> Result<AcpClient, AppError> {
+    let client = AcpClient::spawn_command(&profile.command()?, cwd)?.with_timeouts(timeouts);
+    match &profile.quirks.client_info {
+        Some(info) => client.initialize_as(Some(info.clone())).await?,
+        None => client.initialize().await?,
+    };
     Ok(client)
 }
 
+/// Spawn a client and load `session_id` into it.
+pub(crate) async fn spawn_and_load(session_id: &str, cwd: &str, profile: &AgentProfile, timeouts: Timeouts) -> Result<AcpClient, AppError> {
+    load_into(spawn_and_init(cwd, profile, timeouts).await?, session_id, cwd, profile, false).await
+}
+
+/// Load `session_id` into an initialized `client`. When the profile knows how, a session
+/// lock left over from a process that is gone is removed and the load retried; a lock
+/// held by a live process is only removed when the user chose to `take_over`.
+async fn load_into(
+    mut client: AcpClient,
+    session_id: &str,
+    cwd: &str,
+    profile: &AgentProfile,
+    take_over: bool,
+) -> Result<AcpClient, AppError> {
+    match client.load_session(session_id, cwd).await.map_err(|e| profile.load_error(e, session_id)) {
+        Ok(()) => Ok(client),
+        Err(locked @ AppError::SessionLocked { .. }) => {
+            if take_over {
+                profile.release_lock(session_id)?;
+            } else if !profile.release_stale_lock(session_id)? {
+                return Err(locked);
+            }
+            let timeouts = client.timeouts();
+            drop(client);
+            let mut client = spawn_and_init(cwd, profile, timeouts).await?;
+            client.load_session(session_id, cwd).await.map_err(|e| profile.load_error(e, session_id))?;
+            eprintln!("[CMD] Loaded session after lock removal: {}", session_id);
+            Ok(client)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// An initialized process for a new or reloaded session: a warm one from the pool when
+/// there is one, otherwise a fresh one, making room under the live process cap first.
+async fn acquire(cwd: &str, profile: &AgentProfile, state: &AppState) -> Result<AcpClient, AppError> {
+    if let Some(client) = state.pool.claim(cwd, profile).await {
+        return Ok(client);
+    }
+    make_room(state).await;
+    let timeouts = state.settings.lock().await.timeouts;
+    spawn_and_init(cwd, profile, timeouts).await
+}
+
+/// Stop processes until one more fits under `max_live_processes`: warm ones first, then
+/// the least recently used session that is not running a prompt. Busy sessions are never
+/// stopped, so the cap can be exceeded while they all work.
+pub(crate) async fn make_room(state: &AppState) {
+    let max = state.settings.lock().await.max_live_processes;
+    if max == 0 {
+        return;
+    }
+    loop {
+        let live = live_sessions(state).await + state.pool.len().await;
+        if live < max {
+            return;
+        }
+        if !state.pool.evict_one().await && !suspend_idle_session(state).await {
+            eprintln!("[CMD] {} agent processes running, none can be stopped", live);
+            return;
+        }
+    }
+}
+
+/// Sessions with a running process; a crashed one waiting for its restart does not count.
+async fn live_sessions(state: &AppState) -> usize {
+    state.sessions.lock().await.values().filter(|client| !client.is_closed()).count()
+}
+
+/// Kill the least recently used idle session's process; it is reloaded on its next use.
+async fn suspend_idle_session(state: &AppState) -> bool {
+    let (session_id, client) = {
+        let mut sessions = state.sessions.lock().await;
+        let mut meta = state.session_meta.lock().await;
+        // A crashed session is being restarted by its supervisor, not suspended
+        let candidates = sessions.iter()
+            .filter(|(_, client)| !client.is_closed())
+            .map(|(id, client)| (id.as_str(), client.is_busy()));
+        let Some(session_id) = pool::least_recently_used(candidates, &meta) else { return false };
+        let Some(client) = sessions.remove(&session_id) else { return false };
+        if let Some(meta) = meta.get_mut(&session_id) {
+            meta.suspended = Some(client.capabilities());
+        }
+        (session_id, client)
+    };
+    client.kill();
+    eprintln!("[CMD] Suspended idle session: {}", session_id);
+    true
+}
+
+/// Top the pool up with warm processes for the default agent in `cwd`, as far as
+/// `pool_size` and the live process cap allow.
+async fn refill_pool(cwd: &str, state: &AppState) {
+    let settings = state.settings.lock().await.clone();
+    let Ok(profile) = agents::find(&settings, None) else { return };
+    let live = live_sessions(state).await + state.pool.len().await;
+    let room = match settings.max_live_processes {
+        0 => usize::MAX,
+        max => max.saturating_sub(live),
+    };
+    let missing = settings.pool_size.saturating_sub(state.pool.ready(cwd, &profile).await);
+    state.pool.refill(cwd.to_string(), profile, settings.timeouts, missing.min(room));
+}
+
+/// Register a live client for its session, restart it if it crashes, and replace the
+/// warm process it may have taken from the pool.
+async fn register_session(
+    session_id: &str,
+    cwd: String,
+    client: AcpClient,
+    profile: AgentProfile,
+    app: tauri::AppHandle,
+    state: &AppState,
+) {
+    let client = Arc::new(client);
+    state.sessions.lock().await.insert(session_id.to_string(), client.clone());
+    state.session_meta.lock().await.insert(session_id.to_string(), SessionMeta {
+        cwd: cwd.clone(),
+        profile: profile.clone(),
+        last_used: Instant::now(),
+        suspended: None,
+    });
+    supervisor::watch(
+        app,
+        state.clone(),
+        profile,
+        session_id.to_string(),
+        cwd.clone(),
+        client,
+    );
+    refill_pool(&cwd, state).await;
+}
+
+/// The live client of `session_id`, reloading the session first if it was suspended.
+async fn live_client(
+    session_id: &str,
+    app: &tauri::AppHandle,
+    state: &AppState,
+) -> Result<Arc<AcpClient>, AppError> {
+    if let Some(client) = state.sessions.lock().await.get(session_id).cloned() {
+        if let Some(meta) = state.session_meta.lock().await.get_mut(session_id) {
+            meta.last_used = Instant::now();
+        }
+        return Ok(client);
+    }
+    let meta = state.session_meta.lock().await.get(session_id).cloned()
+        .filter(|meta| meta.suspended.is_some())
+        .ok_or_else(|| not_found(session_id))?;
+    eprintln!("[CMD] Resuming suspended session: {}", session_id);
+    let client = acquire(&meta.cwd, &meta.profile, state).await?;
+    let client = load_into(client, session_id, &meta.cwd, &meta.profile, false).await?;
+    // Another command may have resumed it meanwhile; ours goes away with `client`
+    if let Some(current) = state.sessions.lock().await.get(session_id).cloned() {
+        return Ok(current);
+    }
+    register_session(session_id, meta.cwd, client, meta.profile, app.clone(), state).await;
+    state.sessions.lock().await.get(session_id).cloned().ok_or_else(|| not_found(session_id))
+}
+
+/// Stop the process behind `session_id` and reload the session in a new one, e.g.
+/// when the agent stopped responding.
+#[tauri::command]
+pub async fn restart_session(
+    session_id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let client = state.sessions.lock().await.remove(&session_id).ok_or_else(|| not_found(&session_id))?;
+    if let Some(meta) = state.session_meta.lock().await.get_mut(&session_id) {
+        meta.suspended = Some(client.capabilities());
+    }
+    client.kill();
+    state.interrupted_prompts.lock().await.remove(&session_id);
+    eprintln!("[CMD] Restarting session: {}", session_id);
+    live_client(&session_id, &app, &state).await?;
+    Ok(())
+}
+
+/// Start a session with the agent `agent_id`, or the default agent when `None`.
 #[tauri::command]
 pub async fn new_acp_session(
+    agent_id: Option<String>,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let cwd = get_cwd(&state.workspace.lock().await)?;
-    let mut client = spawn_and_init(&cwd).await?;
-    let session_id = client.new_session(&cwd)?;
-    eprintln!("[CMD] New session: {}", session_id);
-
-    let cancel_handle = client.cancel_handle();
-    let mut sessions = state.sessions.lock().await;
-    sessions.insert(session_id.clone(), Arc::new(Mutex::new(client)));
-    drop(sessions);
+    let profile = agents::find(&*state.settings.lock().await, agent_id.as_deref())?;
+    let mut client = acquire(&cwd, &profile, &state).await?;
+    let session_id = client.new_session(&cwd).await?;
+    eprintln!("[CMD] New {} session: {}", profile.id, session_id);
 
-    // Store cancel handle
-    state.cancel_handles.lock().await.insert(session_id.clone(), (cancel_handle, session_id.clone()));
+    register_session(&session_id, cwd, client, profile, app, &state).await;
     Ok(session_id)
 }
 
+/// Reload `session_id` from the agent. When a live process holds the session this fails
+/// with `SessionLocked`, unless `take_over` says to remove its lock anyway.
 #[tauri::command]
 pub async fn load_acp_session(
     session_id: String,
+    agent_id: Option<String>,
+    take_over: Option<bool>,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     if state.sessions.lock().await.contains_key(&session_id) {
         return Ok(session_id);
     }
+    if state.session_meta.lock().await.contains_key(&session_id) {
+        live_client(&session_id, &app, &state).await?;
+        return Ok(session_id);
+    }
 
     let cwd = get_cwd(&state.workspace.lock().await)?;
-    let mut client = spawn_and_init(&cwd).await?;
-
-    match client.load_session(&session_id, &cwd) {
-        Ok(()) => {
-            eprintln!("[CMD] Loaded session: {}", session_id);
-            let cancel_handle = client.cancel_handle();
-            state.sessions.lock().await.insert(session_id.clone(), Arc::new(Mutex::new(client)));
-            state.cancel_handles.lock().await.insert(session_id.clone(), (cancel_handle, session_id.clone()));
-            Ok(session_id)
-        }
-        Err(e) if e.contains("active in another process") => {
-            drop(client);
-            let lock_path = dirs::home_dir()
-                .ok_or("Cannot find home dir")?
-                .join(".kiro/sessions/cli")
-                .join(format!("{}.lock", session_id));
-            if lock_path.exists() {
-                eprintln!("[CMD] Removing stale lock: {}", lock_path.display());
-                let _ = std::fs::remove_file(&lock_path);
-            }
-            let mut client2 = spawn_and_init(&cwd).await?;
-            client2.load_session(&session_id, &cwd)?;
-            eprintln!("[CMD] Loaded session after lock removal: {}", session_id);
-            let cancel_handle = client2.cancel_handle();
-            state.sessions.lock().await.insert(session_id.clone(), Arc::new(Mutex::new(client2)));
-            state.cancel_handles.lock().await.insert(session_id.clone(), (cancel_handle, session_id.clone()));
-            Ok(session_id)
-        }
-        Err(e) => Err(e)
-    }
+    let profile = agents::find(&*state.settings.lock().await, agent_id.as_deref())?;
+    let client = acquire(&cwd, &profile, &state).await?;
+    let client = load_into(client, &session_id, &cwd, &profile, take_over.unwrap_or(false)).await?;
+    eprintln!("[CMD] Loaded session: {}", session_id);
+    register_session(&session_id, cwd, client, profile, app, &state).await;
+    Ok(session_id)
 }
 
 #[tauri::command]
 pub async fn send_prompt(
     session_id: String,
-    message: String,
-    context_files: Vec<String>,
+    prompt: Vec<PromptPart>,
     app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
-    let client_arc = {
-        let sessions = state.sessions.lock().await;
-        sessions.get(&session_id).cloned().ok_or("Session not found")?
+) -> Result<PromptOutcome, AppError> {
+    run_prompt(session_id, prompt, app, &state).await
+}
+
+/// Send the prompt a crash interrupted again, once its session has been restarted.
+#[tauri::command]
+pub async fn retry_prompt(
+    session_id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<PromptOutcome, AppError> {
+    let prompt = state.interrupted_prompts.lock().await.get(&session_id).cloned()
+        .ok_or_else(|| AppError::NoInterruptedPrompt { session_id: session_id.clone() })?;
+    run_prompt(session_id, prompt, app, &state).await
+}
+
+/// Drop the interrupted prompt when the user does not want it sent again.
+#[tauri::command]
+pub async fn discard_interrupted_prompt(
+    session_id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.interrupted_prompts.lock().await.remove(&session_id);
+    Ok(())
+}
+
+async fn run_prompt(
+    session_id: String,
+    prompt: Vec<PromptPart>,
+    app: tauri::AppHandle,
+    state: &AppState,
+) -> Result<PromptOutcome, AppError> {
+    let client = live_client(&session_id, &app, state).await?;
+    let cwd = get_cwd(&state.workspace.lock().await)?;
+    let capabilities = client.agent_capabilities().prompt_capabilities;
+    let parts = prompt.clone();
+    let blocks = tokio::task::spawn_blocking(move || {
+        prompt::build_blocks(&parts, std::path::Path::new(&cwd), &capabilities)
+    })
+    .await
+    .map_err(AppError::internal)??;
+
+    // Kept until the turn ends, so a crash can offer to send it again
+    state.interrupted_prompts.lock().await.insert(session_id.clone(), prompt);
+    let policies = state.permission_policies.lock().await.clone();
+    let result = client.prompt_streaming(blocks, &session_id, &policies, &app).await;
+    let finished = match &result {
+        Ok(outcome) => outcome.stop_reason.is_some(),
+        Err(_) => !client.is_closed(),
     };
+    if finished {
+        state.interrupted_prompts.lock().await.remove(&session_id);
+    }
+    let outcome = result?;
 
-    let mut client = client_arc.lock().await;
-
-    let mut prompt = String::new();
-    if !context_files.is_empty() {
-        prompt.push_str("Here are the documents for context:\n\n");
-        for f in &context_files {
-            let name = std::path::Path::new(f).file_name().unwrap_or_default().to_string_lossy();
-            match file_reader::read_file(f) {
-                Ok(content) => {
-                    let truncated = if content.len() > 50000 {
-                        format!("{}...\n[truncated]", &content[..50000])
-                    } else {
-                        content
-                    };
-                    prompt.push_str(&format!("--- {} ---\n{}\n\n", name, truncated));
-                }
-                Err(e) => {
-                    prompt.push_str(&format!("--- {} ---\n[Error reading: {}]\n\n", name, e));
-                }
-            }
-        }
-        prompt.push_str("---\n\nUser question: ");
+    // Kept until the next save_session_history writes it to the notebook
+    let mut record = serde_json::to_value(&outcome).map_err(AppError::internal)?;
+    if let Some(record) = record.as_object_mut() {
+        record.remove("text");
+        record.insert("finishedAt".to_string(), chrono::Local::now().to_rfc3339().into());
     }
-    prompt.push_str(&message);
+    state.unsaved_turns.lock().await.entry(session_id).or_default().push(record);
+    Ok(outcome)
+}
+
+#[tauri::command]
+pub async fn respond_permission(
+    session_id: String,
+    request_id: RequestId,
+    option_id: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let client = {
+        let sessions = state.sessions.lock().await;
+        sessions.get(&session_id).cloned().ok_or_else(|| not_found(&session_id))?
+    };
+    client.respond_permission(&request_id, option_id.as_deref()).await
+}
 
-    client.prompt_streaming(&prompt, &session_id, &app)
+#[tauri::command]
+pub async fn get_permission_policies(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, PermissionPolicy>, AppError> {
+    Ok(state.permission_policies.lock().await.clone())
+}
+
+#[tauri::command]
+pub async fn set_permission_policy(
+    kind: String,
+    policy: PermissionPolicy,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let mut policies = state.permission_policies.lock().await;
+    if policy == PermissionPolicy::Ask {
+        policies.remove(&kind);
+    } else {
+        policies.insert(kind, policy);
+    }
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn cancel_prompt(
     session_id: String,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let handles = state.cancel_handles.lock().await;
-    let (stdin, sid) = handles.get(&session_id).ok_or("Session not found")?;
-    acp::send_cancel(stdin, sid, 99999)
+) -> Result<(), AppError> {
+    // A suspended session has nothing running
+    let Some(client) = state.sessions.lock().await.get(&session_id).cloned() else {
+        return Ok(());
+    };
+    client.cancel(&session_id).await?;
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn set_model(
     session_id: String,
     model_id: String,
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let client_arc = {
-        let sessions = state.sessions.lock().await;
-        sessions.get(&session_id).cloned().ok_or("Session not found")?
+) -> Result<(), AppError> {
+    let client = live_client(&session_id, &app, &state).await?;
+    client.set_model(&model_id).await
+}
+
+#[tauri::command]
+pub async fn set_mode(
+    session_id: String,
+    mode_id: String,
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    let client = live_client(&session_id, &app, &state).await?;
+    client.set_mode(&mode_id).await
+}
+
+#[tauri::command]
+pub async fn get_session_capabilities(
+    session_id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<SessionCapabilities, AppError> {
+    if let Some(client) = state.sessions.lock().await.get(&session_id) {
+        return Ok(client.capabilities());
+    }
+    // Suspended: what it offered before, without reloading it just to look
+    state.session_meta.lock().await.get(&session_id)
+        .and_then(|meta| meta.suspended.clone())
+        .ok_or_else(|| not_found(&session_id))
+}
+
+#[tauri::command]
+pub async fn get_session_diagnostics(
+    session_id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Diagnostics, AppError> {
+    let sessions = state.sessions.lock().await;
+    let Some(client) = sessions.get(&session_id) else {
+        return match state.session_meta.lock().await.get(&session_id) {
+            Some(_) => Err(AppError::SessionSuspended { session_id }),
+            None => Err(not_found(&session_id)),
+        };
     };
-    let mut client = client_arc.lock().await;
-    client.set_model(&model_id)
+    Ok(client.diagnostics())
 }
 
 #[tauri::command]
 pub async fn close_acp_session(
     session_id: String,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    state.cancel_handles.lock().await.remove(&session_id);
-    if let Some(client_arc) = state.sessions.lock().await.remove(&session_id) {
-        let mut client = client_arc.lock().await;
+) -> Result<(), AppError> {
+    state.interrupted_prompts.lock().await.remove(&session_id);
+    state.session_meta.lock().await.remove(&session_id);
+    let client = state.sessions.lock().await.remove(&session_id);
+    if let Some(client) = client {
         client.kill();
         eprintln!("[CMD] Closed session: {}", session_id);
     }
@@ -227,29 +513,45 @@ pub async fn save_session_history(
     label: String,
     messages: String,
     context_files: Vec<String>,
+    agent_id: Option<String>,
     state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let cwd = get_cwd(&state.workspace.lock().await)?;
 
     let dir = std::path::Path::new(&cwd).join(".kiro-notebook");
-    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| AppError::io(&dir, e))?;
 
     let path = dir.join(format!("{}.json", session_id));
+
+    // Per-turn outcomes (stop reason, usage, credits) accumulate across saves
+    let mut turns: Vec<serde_json::Value> = std::fs::read_to_string(&path).ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|existing| existing.get("turns").and_then(|t| t.as_array()).cloned())
+        .unwrap_or_default();
+    let mut unsaved_turns = state.unsaved_turns.lock().await;
+    let new_turns = unsaved_turns.remove(&session_id).unwrap_or_default();
+    turns.extend(new_turns.iter().cloned());
+
     let data = serde_json::json!({
         "sessionId": session_id,
         "label": label,
         "messages": serde_json::from_str::<serde_json::Value>(&messages).unwrap_or_default(),
         "contextFiles": context_files,
+        "agentId": agent_id,
+        "turns": turns,
         "updatedAt": chrono::Local::now().to_rfc3339(),
     });
-    std::fs::write(&path, serde_json::to_string_pretty(&data).unwrap())
-        .map_err(|e| e.to_string())
+    if let Err(e) = std::fs::write(&path, serde_json::to_string_pretty(&data).unwrap()) {
+        unsaved_turns.insert(session_id, new_turns);
+        return Err(AppError::io(&path, e));
+    }
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn load_session_history(
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let cwd = get_cwd(&state.workspace.lock().await)?;
 
     let dir = std::path::Path::new(&cwd).join(".kiro-notebook");
@@ -279,25 +581,81 @@ pub async fn load_session_history(
     Ok(serde_json::to_string(&sessions).unwrap())
 }
 
-fn find_kiro_cli() -> Result<String, String> {
-    let candidates = [
-        "/Applications/Kiro CLI.app/Contents/MacOS/kiro-cli",
-        &format!("{}/.local/bin/kiro-cli", std::env::var("HOME").unwrap_or_default()),
-        "/usr/local/bin/kiro-cli",
-    ];
+/// Agents a session can be started with, the built-in Kiro profile first.
+#[tauri::command]
+pub async fn list_agents(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<AgentProfile>, AppError> {
+    Ok(agents::profiles(&*state.settings.lock().await))
+}
+
+#[tauri::command]
+pub async fn get_settings(
+    state: tauri::State<'_, AppState>,
+) -> Result<Settings, AppError> {
+    Ok(state.settings.lock().await.clone())
+}
 
-    for path in &candidates {
-        if std::path::Path::new(path).exists() {
-            return Ok(path.to_string());
-        }
+/// Persist `settings`; sessions started from now on use them.
+#[tauri::command]
+pub async fn save_settings(
+    settings: Settings,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), AppError> {
+    settings::save(&settings)?;
+    *state.settings.lock().await = settings;
+    // Warm processes may have been started with what just changed
+    state.pool.clear().await;
+    if let Some(cwd) = state.workspace.lock().await.clone() {
+        refill_pool(&cwd, &state).await;
     }
+    Ok(())
+}
 
-    if let Ok(output) = std::process::Command::new("which").arg("kiro-cli").output() {
-        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !path.is_empty() && std::path::Path::new(&path).exists() {
-            return Ok(path);
-        }
-    }
+/// What `check_kiro_cli` found out about the configured agent.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliCheck {
+    pub path: String,
+    pub args: Vec<String>,
+    pub protocol_version: u32,
+    pub agent_info: Option<Implementation>,
+    pub agent_capabilities: AgentCapabilities,
+}
 
-    Err("kiro-cli not found. Please install Kiro CLI.".to_string())
+/// Start kiro-cli with `settings` (the saved ones when `None`) and run `initialize`,
+/// so a broken setup shows up before the first chat.
+#[tauri::command]
+pub async fn check_kiro_cli(
+    settings: Option<KiroCliSettings>,
+    state: tauri::State<'_, AppState>,
+) -> Result<CliCheck, AppError> {
+    let settings = match settings {
+        Some(settings) => settings,
+        None => state.settings.lock().await.kiro_cli.clone(),
+    };
+    let profile = AgentProfile::kiro(&settings);
+    let command = profile.command()?;
+    // The trace of the check lands in the workspace when one is open
+    let cwd = match state.workspace.lock().await.clone() {
+        Some(workspace) => workspace,
+        None => std::env::temp_dir().display().to_string(),
+    };
+    let timeouts = state.settings.lock().await.timeouts;
+    let client = AcpClient::spawn_command(&command, &cwd)?.with_timeouts(timeouts);
+    let result = client.initialize_as(profile.quirks.client_info.clone()).await;
+    let stderr = client.stderr_tail();
+    client.kill();
+    let response = match result {
+        Ok(response) => response,
+        Err(e) if stderr.is_empty() => return Err(e),
+        Err(e) => return Err(AppError::CliCheckFailed { cause: Box::new(e), stderr }),
+    };
+    Ok(CliCheck {
+        path: command.program,
+        args: command.args,
+        protocol_version: response.protocol_version,
+        agent_info: response.agent_info,
+        agent_capabilities: response.agent_capabilities,
+    })
 }
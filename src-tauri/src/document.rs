@@ -0,0 +1,333 @@
+use std::ops::Range;
+
+use crate::error::AppError;
+
+/// A file's content as blocks, whatever format it was read from. `to_markdown` turns
+/// it back into text for prompts, previews and MCP results.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub metadata: Metadata,
+    pub blocks: Vec<Block>,
+}
+
+/// Rendered by `to_markdown` as a header above the blocks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Metadata {
+    /// The title as a heading unless `blocks` open with it already, then
+    /// "By <author>" and the description as a quote.
+    fn header(&self, blocks: &[Block]) -> Vec<Block> {
+        let opens_with_title = match (&self.title, blocks.first().map(|block| &block.kind)) {
+            (Some(title), Some(BlockKind::Heading { text, .. })) => text.eq_ignore_ascii_case(title),
+            _ => false,
+        };
+        let mut header = Vec::new();
+        if let Some(text) = self.title.clone().filter(|_| !opens_with_title) {
+            header.push(Block::new(BlockKind::Heading { level: 1, text }));
+        }
+        if let Some(author) = &self.author {
+            header.push(Block::new(BlockKind::Paragraph { text: format!("By {}", author) }));
+        }
+        if let Some(text) = self.description.clone() {
+            header.push(Block::new(BlockKind::Quote { text }));
+        }
+        header
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub kind: BlockKind,
+    /// Byte range in the file, for formats read as text (Markdown, plain text)
+    pub source: Option<Range<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockKind {
+    /// `level` 1 to 6
+    Heading { level: u8, text: String },
+    Paragraph { text: String },
+    /// `depth` 0 for a top-level item; ordered items are numbered when rendered
+    ListItem { ordered: bool, depth: usize, text: String },
+    /// The first row is the header
+    Table { rows: Vec<Vec<String>> },
+    CodeBlock { language: Option<String>, code: String },
+    Quote { text: String },
+    /// Page `number` (1-based) starts here
+    PageBreak { number: u32 },
+}
+
+impl Block {
+    pub fn new(kind: BlockKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    pub fn at(kind: BlockKind, source: Range<usize>) -> Self {
+        Self { kind, source: Some(source) }
+    }
+}
+
+impl Document {
+    pub fn new(blocks: Vec<Block>) -> Self {
+        Self { metadata: Metadata::default(), blocks }
+    }
+
+    /// Number of pages; 0 for formats without pages.
+    pub fn page_count(&self) -> usize {
+        self.blocks.iter().filter(|b| matches!(b.kind, BlockKind::PageBreak { .. })).count()
+    }
+
+    /// The metadata header, then the blocks.
+    pub fn to_markdown(&self) -> String {
+        let mut blocks = self.metadata.header(&self.blocks);
+        blocks.extend(self.blocks.iter().cloned());
+        render(&blocks)
+    }
+
+    /// Each page's Markdown without its marker, with its number; one unnumbered
+    /// entry with everything for formats without pages.
+    pub fn pages(&self) -> Vec<(Option<u32>, String)> {
+        if self.page_count() == 0 {
+            return vec![(None, self.to_markdown())];
+        }
+        let mut pages: Vec<(Option<u32>, Vec<Block>)> = Vec::new();
+        for block in &self.blocks {
+            match (&block.kind, pages.last_mut()) {
+                (BlockKind::PageBreak { number }, _) => pages.push((Some(*number), Vec::new())),
+                (_, Some((_, blocks))) => blocks.push(block.clone()),
+                // Anything before the first page break is not on a page
+                (_, None) => {}
+            }
+        }
+        pages.into_iter().map(|(number, blocks)| (number, render(&blocks))).collect()
+    }
+
+    /// Pages `from` to `to` (1-based, inclusive) as `to_markdown` renders them.
+    pub fn page_range(&self, from: u32, to: u32) -> Result<String, AppError> {
+        let pages = self.page_count() as u32;
+        if pages == 0 || from == 0 || from > to || from > pages {
+            return Err(AppError::PageOutOfRange { from, to, pages });
+        }
+        let mut page = 0;
+        let blocks: Vec<Block> = self.blocks.iter()
+            .filter(|block| {
+                if let BlockKind::PageBreak { number } = block.kind {
+                    page = number;
+                }
+                page >= from && page <= to
+            })
+            .cloned()
+            .collect();
+        Ok(render(&blocks))
+    }
+}
+
+/// Markdown for `blocks`, separated by blank lines except between items of one list.
+fn render(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    // Whether the list at each depth is ordered, and its items so far
+    let mut counters: Vec<(bool, u32)> = Vec::new();
+    let mut in_list = false;
+    for block in blocks {
+        let is_item = matches!(block.kind, BlockKind::ListItem { .. });
+        if !out.is_empty() {
+            out.push_str(if is_item && in_list { "\n" } else { "\n\n" });
+        }
+        if !is_item {
+            counters.clear();
+        }
+        in_list = is_item;
+        match &block.kind {
+            BlockKind::Heading { level, text } => {
+                out.push_str(&"#".repeat((*level).clamp(1, 6) as usize));
+                out.push(' ');
+                out.push_str(&one_line(text));
+            }
+            BlockKind::Paragraph { text } => out.push_str(text.trim_end()),
+            BlockKind::ListItem { ordered, depth, text } => {
+                counters.resize(depth + 1, (*ordered, 0));
+                // A bulleted list after a numbered one at the same depth is a new list
+                if counters[*depth].0 != *ordered {
+                    counters[*depth] = (*ordered, 0);
+                }
+                counters[*depth].1 += 1;
+                // Three spaces nest under both `- ` and `1. `
+                let indent = "   ".repeat(*depth);
+                let marker = if *ordered { format!("{}. ", counters[*depth].1) } else { "- ".to_string() };
+                // Continuation lines line up with the item's text
+                let continuation = format!("\n{}{}", indent, " ".repeat(marker.len()));
+                out.push_str(&indent);
+                out.push_str(&marker);
+                out.push_str(&text.trim_end().replace('\n', &continuation));
+            }
+            BlockKind::Table { rows } => render_table(rows, &mut out),
+            BlockKind::CodeBlock { language, code } => {
+                // A fence longer than any run of backticks in the code
+                let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                out.push_str(&fence);
+                out.push_str(language.as_deref().unwrap_or(""));
+                out.push('\n');
+                out.push_str(code.trim_end_matches('\n'));
+                out.push('\n');
+                out.push_str(&fence);
+            }
+            BlockKind::Quote { text } => {
+                let quoted: Vec<String> = text.trim_end().lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect();
+                out.push_str(&quoted.join("\n"));
+            }
+            BlockKind::PageBreak { number } => out.push_str(&format!("[page {}]", number)),
+        }
+    }
+    out
+}
+
+fn render_table(rows: &[Vec<String>], out: &mut String) {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if columns == 0 {
+        return;
+    }
+    let row = |cells: &[String]| {
+        let mut line = String::from("|");
+        for i in 0..columns {
+            let cell = cells.get(i).map(String::as_str).unwrap_or("");
+            line.push(' ');
+            line.push_str(&one_line(cell).replace('|', "\\|"));
+            line.push_str(" |");
+        }
+        line
+    };
+    let mut lines = vec![row(&rows[0]), format!("|{}", " --- |".repeat(columns))];
+    lines.extend(rows[1..].iter().map(|cells| row(cells)));
+    out.push_str(&lines.join("\n"));
+}
+
+/// `[text](href)`, with the destination in `<>` when it would end the link early.
+pub fn markdown_link(text: &str, href: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() {
+        return String::new();
+    }
+    if href.contains([' ', '(', ')']) {
+        format!("[{}](<{}>)", text, href)
+    } else {
+        format!("[{}]({})", text, href)
+    }
+}
+
+/// `text` on one line, for headings and table cells
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(ordered: bool, depth: usize, text: &str) -> Block {
+        Block::new(BlockKind::ListItem { ordered, depth, text: text.to_string() })
+    }
+
+    fn blocks_without_pages() -> Vec<Block> {
+        vec![Block::new(BlockKind::Paragraph { text: "text".to_string() })]
+    }
+
+    #[test]
+    fn renders_every_kind_of_block() {
+        let document = Document::new(vec![
+            Block::new(BlockKind::Heading { level: 2, text: "Results".to_string() }),
+            Block::new(BlockKind::Paragraph { text: "Two findings.".to_string() }),
+            item(true, 0, "first"),
+            item(false, 1, "detail\nover two lines"),
+            item(true, 0, "second"),
+            Block::new(BlockKind::Table { rows: vec![
+                vec!["Name".to_string(), "Value".to_string()],
+                vec!["a|b".to_string()],
+            ] }),
+            Block::new(BlockKind::CodeBlock { language: Some("rust".to_string()), code: "let x = 1;\n".to_string() }),
+            Block::new(BlockKind::Quote { text: "quoted\n\nagain".to_string() }),
+        ]);
+        assert_eq!(document.to_markdown(), "\
+## Results
+
+Two findings.
+
+1. first
+   - detail
+     over two lines
+2. second
+
+| Name | Value |
+| --- | --- |
+| a\\|b |  |
+
+```rust
+let x = 1;
+```
+
+> quoted
+>
+> again");
+    }
+
+    #[test]
+    fn metadata_heads_the_markdown() {
+        let mut document = Document::new(vec![Block::new(BlockKind::Paragraph { text: "Body".to_string() })]);
+        document.metadata = Metadata {
+            title: Some("Report".to_string()),
+            author: Some("A. Author".to_string()),
+            description: Some("What we found".to_string()),
+        };
+        assert_eq!(document.to_markdown(), "# Report\n\nBy A. Author\n\n> What we found\n\nBody");
+
+        document.blocks.insert(0, Block::new(BlockKind::Heading { level: 2, text: "report".to_string() }));
+        document.metadata.author = None;
+        document.metadata.description = None;
+        assert_eq!(document.to_markdown(), "## report\n\nBody");
+    }
+
+    #[test]
+    fn numbering_restarts_after_other_blocks() {
+        let document = Document::new(vec![
+            item(true, 0, "a"),
+            item(true, 0, "b"),
+            Block::new(BlockKind::Paragraph { text: "between".to_string() }),
+            item(true, 0, "c"),
+        ]);
+        assert_eq!(document.to_markdown(), "1. a\n2. b\n\nbetween\n\n1. c");
+        let switched = Document::new(vec![item(false, 0, "a"), item(true, 0, "b")]);
+        assert_eq!(switched.to_markdown(), "- a\n1. b");
+    }
+
+    #[test]
+    fn code_fence_outgrows_backticks_in_the_code() {
+        let document = Document::new(vec![Block::new(BlockKind::CodeBlock { language: None, code: "```\nnested\n```".to_string() })]);
+        assert_eq!(document.to_markdown(), "````\n```\nnested\n```\n````");
+    }
+
+    #[test]
+    fn page_range_renders_only_those_pages() {
+        let mut blocks = Vec::new();
+        for (number, text) in (1..).zip(["one", "two", "three"]) {
+            blocks.push(Block::new(BlockKind::PageBreak { number }));
+            blocks.push(Block::new(BlockKind::Paragraph { text: text.to_string() }));
+        }
+        let document = Document::new(blocks);
+        assert_eq!(document.page_count(), 3);
+        assert_eq!(document.to_markdown(), "[page 1]\n\none\n\n[page 2]\n\ntwo\n\n[page 3]\n\nthree");
+        assert_eq!(document.page_range(2, 3).unwrap(), "[page 2]\n\ntwo\n\n[page 3]\n\nthree");
+        assert_eq!(document.page_range(3, 9).unwrap(), "[page 3]\n\nthree");
+        assert!(document.page_range(4, 5).is_err());
+        assert!(document.page_range(2, 1).is_err());
+        assert!(Document::default().page_range(1, 1).is_err());
+        assert_eq!(document.pages()[1], (Some(2), "two".to_string()));
+        assert_eq!(Document::new(blocks_without_pages()).pages(), vec![(None, "text".to_string())]);
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use crate::document::{self, Block, BlockKind, Document};
+use crate::xml::{self, Element, Node};
+
+/// Relationship type of a hyperlink, after the last `/`
+const HYPERLINK: &str = "hyperlink";
+
+/// What the body refers to by id: links, styles and list definitions.
+#[derive(Default)]
+struct Context {
+    /// Relationship id to URL
+    links: HashMap<String, String>,
+    /// Style id to heading level, following `basedOn`
+    headings: HashMap<String, u8>,
+    /// Style id to the list its paragraphs belong to, for styles like "List Number"
+    style_numbering: HashMap<String, (String, usize)>,
+    /// `(numId, ilvl)` of lists numbered rather than bulleted
+    ordered: HashMap<(String, usize), bool>,
+}
+
+/// A `.docx` file as a `Document`: headers first, then the body with tracked insertions
+/// kept and deletions dropped, then footers, then the notes and comments the body marks
+/// as `[^1]` (footnote), `[^e1]` (endnote) and `[^c1]` (comment), as Markdown footnotes.
+/// Text boxes follow the paragraph they are anchored in.
+pub fn read(bytes: &[u8]) -> Result<Document, String> {
+    let mut archive = xml::open_zip(bytes, "DOCX")?;
+    let body = xml::zip_part(&mut archive, "word/document.xml")?
+        .ok_or("Not a Word document: word/document.xml is missing")?;
+
+    let relationships = relationships(&mut archive, "word/_rels/document.xml.rels")?;
+    let mut cx = Context {
+        links: relationships.iter()
+            .filter(|(_, kind, _)| kind == HYPERLINK)
+            .map(|(id, _, target)| (id.clone(), target.clone()))
+            .collect(),
+        ..Context::default()
+    };
+    if let Some(styles) = xml::zip_part(&mut archive, "word/styles.xml")? {
+        read_styles(&styles, &mut cx);
+    }
+    if let Some(numbering) = xml::zip_part(&mut archive, "word/numbering.xml")? {
+        cx.ordered = read_numbering(&numbering);
+    }
+
+    // Each part once, in the order Word numbers them (header1, header2, ..., header10)
+    let mut parts: Vec<(&str, String)> = relationships.iter()
+        .filter(|(_, kind, _)| matches!(kind.as_str(), "header" | "footer" | "footnotes" | "endnotes" | "comments"))
+        .map(|(_, kind, target)| (kind.as_str(), word_path(target)))
+        .collect();
+    parts.sort_by_cached_key(|(kind, path)| (*kind, part_number(path), path.clone()));
+    parts.dedup();
+    let mut headers = Vec::new();
+    let mut footers = Vec::new();
+    let (mut footnotes, mut endnotes, mut comments) = (Vec::new(), Vec::new(), Vec::new());
+    for (kind, path) in parts {
+        let Some(root) = xml::zip_part(&mut archive, &path)? else { continue };
+        match kind {
+            "header" => headers.push(section_text(&root, &cx)),
+            "footer" => footers.push(section_text(&root, &cx)),
+            "footnotes" => footnotes = read_notes(&root, "footnote", "", &cx),
+            "endnotes" => endnotes = read_notes(&root, "endnote", "e", &cx),
+            _ => comments = read_notes(&root, "comment", "c", &cx),
+        }
+    }
+    // Sections share headers; they read the same once
+    headers.dedup();
+    footers.dedup();
+
+    let mut blocks: Vec<Block> = headers.into_iter().flatten()
+        .map(|text| Block::new(BlockKind::Paragraph { text: format!("Header: {}", text) }))
+        .collect();
+    if let Some(body) = body.child("body") {
+        body_blocks(body, &cx, &mut blocks);
+    }
+    blocks.extend(footers.into_iter().flatten().map(|text| Block::new(BlockKind::Paragraph { text: format!("Footer: {}", text) })));
+    let notes = footnotes.into_iter().chain(endnotes).chain(comments);
+    blocks.extend(notes.map(|text| Block::new(BlockKind::Paragraph { text })));
+
+    let mut document = Document::new(blocks);
+    if let Some(core) = xml::zip_part(&mut archive, "docProps/core.xml")? {
+        let field = |name: &str| core.child(name).map(|e| e.text().trim().to_string()).filter(|text| !text.is_empty());
+        document.metadata.title = field("title");
+        document.metadata.author = field("creator");
+        document.metadata.description = field("description");
+    }
+    Ok(document)
+}
+
+/// `(id, type, target)` of each relationship, with the type cut to its last segment.
+fn relationships<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, path: &str) -> Result<Vec<(String, String, String)>, String> {
+    let Some(root) = xml::zip_part(archive, path)? else { return Ok(Vec::new()) };
+    Ok(root.children_named("Relationship")
+        .filter_map(|rel| {
+            let kind = rel.attr("Type")?.rsplit('/').next()?.to_string();
+            Some((rel.attr("Id")?.to_string(), kind, rel.attr("Target")?.to_string()))
+        })
+        .collect())
+}
+
+/// The number Word puts at the end of a part's name, `10` for `word/header10.xml`
+fn part_number(path: &str) -> Option<u32> {
+    let stem = path.strip_suffix(".xml").unwrap_or(path);
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().ok()
+}
+
+/// Package path of a target relative to `word/document.xml`
+fn word_path(target: &str) -> String {
+    match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("word/{}", target),
+    }
+}
+
+fn read_styles(styles: &Element, cx: &mut Context) {
+    // Own outline level and parent of each style, resolved below
+    let mut own: HashMap<&str, (Option<u8>, Option<&str>)> = HashMap::new();
+    for style in styles.children_named("style") {
+        let Some(id) = style.attr("styleId") else { continue };
+        let properties = style.child("pPr");
+        let level = properties.and_then(|p| p.child("outlineLvl")).and_then(outline_level)
+            .or_else(|| style.child("name").and_then(|n| n.attr("val")).and_then(heading_level))
+            .or_else(|| heading_level(id));
+        let based_on = style.child("basedOn").and_then(|b| b.attr("val"));
+        own.insert(id, (level, based_on));
+        if let Some(numbering) = properties.and_then(|p| p.child("numPr")).and_then(numbering) {
+            cx.style_numbering.insert(id.to_string(), numbering);
+        }
+    }
+    for &id in own.keys() {
+        let mut current = Some(id);
+        // Ten steps is deeper than any real chain and stops cycles
+        for _ in 0..10 {
+            let Some((level, based_on)) = current.and_then(|id| own.get(id)) else { break };
+            if let Some(level) = level {
+                cx.headings.insert(id.to_string(), *level);
+                break;
+            }
+            current = *based_on;
+        }
+    }
+}
+
+/// Which levels of which lists are numbered: any format but bullets is.
+fn read_numbering(numbering: &Element) -> HashMap<(String, usize), bool> {
+    let formats = |levels: &Element| -> Vec<(usize, bool)> {
+        levels.children_named("lvl")
+            .filter_map(|lvl| {
+                let level = lvl.attr("ilvl")?.parse().ok()?;
+                let format = lvl.child("numFmt")?.attr("val")?;
+                Some((level, !matches!(format, "bullet" | "none")))
+            })
+            .collect()
+    };
+    let abstract_formats: HashMap<&str, Vec<(usize, bool)>> = numbering.children_named("abstractNum")
+        .filter_map(|list| Some((list.attr("abstractNumId")?, formats(list))))
+        .collect();
+
+    let mut ordered = HashMap::new();
+    for num in numbering.children_named("num") {
+        let Some(id) = num.attr("numId") else { continue };
+        let abstract_id = num.child("abstractNumId").and_then(|a| a.attr("val")).unwrap_or("");
+        for (level, numbered) in abstract_formats.get(abstract_id).into_iter().flatten() {
+            ordered.insert((id.to_string(), *level), *numbered);
+        }
+        // Overrides replace single levels of the shared definition
+        for level_override in num.children_named("lvlOverride") {
+            if let Some(lvl) = level_override.child("lvl") {
+                let levels = Element { children: vec![Node::Element(lvl.clone())], ..Element::default() };
+                for (level, numbered) in formats(&levels) {
+                    ordered.insert((id.to_string(), level), numbered);
+                }
+            }
+        }
+    }
+    ordered
+}
+
+/// `w:outlineLvl` counts from 0, and 9 means body text
+fn outline_level(element: &Element) -> Option<u8> {
+    let level: u8 = element.attr("val")?.parse().ok()?;
+    (level < 9).then_some(level.min(5) + 1)
+}
+
+/// `Heading 1` to `Heading 9` (or the style id `Heading1`) and `Title`; levels below 6
+/// are rendered as 6.
+fn heading_level(style: &str) -> Option<u8> {
+    let style = style.to_lowercase().replace(' ', "");
+    if style == "title" {
+        return Some(1);
+    }
+    let level: u8 = style.strip_prefix("heading")?.parse().ok()?;
+    (1..=9).contains(&level).then_some(level.min(6))
+}
+
+/// `(numId, ilvl)` of `w:numPr`; `numId` 0 takes a paragraph out of its list
+fn numbering(properties: &Element) -> Option<(String, usize)> {
+    let id = properties.child("numId")?.attr("val")?;
+    let level = properties.child("ilvl").and_then(|l| l.attr("val")).and_then(|v| v.parse().ok()).unwrap_or(0);
+    (id != "0").then(|| (id.to_string(), level))
+}
+
+/// Paragraphs and tables of the body, a table cell, a text box or a note.
+fn body_blocks(parent: &Element, cx: &Context, blocks: &mut Vec<Block>) {
+    for child in parent.elements() {
+        match child.name.as_str() {
+            "p" => paragraph(child, cx, blocks),
+            "tbl" => table(child, cx, blocks),
+            "sdt" => {
+                if let Some(content) = child.child("sdtContent") {
+                    body_blocks(content, cx, blocks);
+                }
+            }
+            "AlternateContent" => {
+                if let Some(content) = alternate(child) {
+                    body_blocks(content, cx, blocks);
+                }
+            }
+            "customXml" | "ins" | "moveTo" => body_blocks(child, cx, blocks),
+            _ => {}
+        }
+    }
+}
+
+/// The first choice of `mc:AlternateContent`, or its fallback; they hold the same
+/// content, so reading both would read it twice.
+fn alternate(element: &Element) -> Option<&Element> {
+    element.child("Choice").or_else(|| element.child("Fallback"))
+}
+
+fn paragraph(p: &Element, cx: &Context, blocks: &mut Vec<Block>) {
+    let mut text = String::new();
+    let mut boxes = Vec::new();
+    inline(p, cx, &mut text, &mut boxes);
+    let text = text.trim().to_string();
+
+    if !text.is_empty() {
+        let properties = p.child("pPr");
+        let style = properties.and_then(|p| p.child("pStyle")).and_then(|s| s.attr("val"));
+        let level = properties.and_then(|p| p.child("outlineLvl")).and_then(outline_level)
+            .or_else(|| style.and_then(|s| cx.headings.get(s).copied()))
+            .or_else(|| style.and_then(heading_level));
+        let list = properties.and_then(|p| p.child("numPr")).and_then(numbering)
+            .or_else(|| style.and_then(|s| cx.style_numbering.get(s).cloned()));
+        // Numbered headings stay headings
+        let kind = match (level, list) {
+            (Some(level), _) => BlockKind::Heading { level, text },
+            (None, Some((id, depth))) => {
+                let ordered = cx.ordered.get(&(id, depth)).copied().unwrap_or(false);
+                BlockKind::ListItem { ordered, depth, text }
+            }
+            (None, None) => BlockKind::Paragraph { text },
+        };
+        blocks.push(Block::new(kind));
+    }
+    blocks.extend(boxes);
+}
+
+/// Text of runs below `element` into `text`, and text boxes found there into `boxes`.
+fn inline(element: &Element, cx: &Context, text: &mut String, boxes: &mut Vec<Block>) {
+    for child in element.elements() {
+        match child.name.as_str() {
+            "t" => text.push_str(&child.text()),
+            "tab" | "ptab" => text.push('\t'),
+            "br" | "cr" => text.push('\n'),
+            "noBreakHyphen" => text.push('-'),
+            "footnoteReference" => note_marker(child, "", text),
+            "endnoteReference" => note_marker(child, "e", text),
+            "commentReference" => note_marker(child, "c", text),
+            "hyperlink" => {
+                let mut label = String::new();
+                inline(child, cx, &mut label, boxes);
+                match child.attr("id").and_then(|id| cx.links.get(id)) {
+                    Some(url) => text.push_str(&document::markdown_link(&label, url)),
+                    None => text.push_str(&label),
+                }
+            }
+            "txbxContent" => body_blocks(child, cx, boxes),
+            "AlternateContent" => {
+                if let Some(content) = alternate(child) {
+                    inline(content, cx, text, boxes);
+                }
+            }
+            // Deleted text, field codes and formatting are not content
+            "del" | "moveFrom" | "instrText" | "delText" | "pPr" | "rPr" => {}
+            // Runs, insertions, smart tags, fields, drawings and the like
+            _ => inline(child, cx, text, boxes),
+        }
+    }
+}
+
+fn note_marker(reference: &Element, prefix: &str, text: &mut String) {
+    if let Some(id) = reference.attr("id") {
+        text.push_str(&format!("[^{}{}]", prefix, id));
+    }
+}
+
+fn table(tbl: &Element, cx: &Context, blocks: &mut Vec<Block>) {
+    let rows: Vec<Vec<String>> = tbl.children_named("tr")
+        .map(|row| {
+            let mut cells = Vec::new();
+            for cell in row.children_named("tc") {
+                let mut content = Vec::new();
+                body_blocks(cell, cx, &mut content);
+                cells.push(Document::new(content).to_markdown());
+                // Merged cells keep the columns after them in place
+                let span: usize = cell.child("tcPr")
+                    .and_then(|p| p.child("gridSpan"))
+                    .and_then(|s| s.attr("val"))
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(1);
+                cells.extend(std::iter::repeat_n(String::new(), span.saturating_sub(1)));
+            }
+            cells
+        })
+        .filter(|cells| !cells.is_empty())
+        .collect();
+    if !rows.is_empty() {
+        blocks.push(Block::new(BlockKind::Table { rows }));
+    }
+}
+
+/// A header or footer as one line of text.
+fn section_text(root: &Element, cx: &Context) -> Option<String> {
+    let mut blocks = Vec::new();
+    body_blocks(root, cx, &mut blocks);
+    let text = Document::new(blocks).to_markdown().split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Footnote, endnote or comment definitions as `[^{prefix}{id}]: text`, skipping the
+/// separators Word keeps among the notes.
+fn read_notes(root: &Element, name: &str, prefix: &str, cx: &Context) -> Vec<String> {
+    root.children_named(name)
+        .filter(|note| note.attr("type").is_none_or(|kind| kind == "normal"))
+        .filter_map(|note| {
+            let id = note.attr("id")?;
+            let mut blocks = Vec::new();
+            body_blocks(note, cx, &mut blocks);
+            let text = Document::new(blocks).to_markdown().split_whitespace().collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                return None;
+            }
+            Some(match note.attr("author") {
+                Some(author) => format!("[^{}{}]: {}: {}", prefix, id, author, text),
+                None => format!("[^{}{}]: {}", prefix, id, text),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMESPACES: &str = concat!(
+        r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+        r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" "#,
+        r#"xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006" "#,
+        r#"xmlns:wps="http://schemas.microsoft.com/office/word/2010/wordprocessingShape" "#,
+        r#"xmlns:v="urn:schemas-microsoft-com:vml""#,
+    );
+
+    /// A `.docx` with `body` in `word/document.xml` and each of `parts` as
+    /// `(relationship type, target, content)`; parts without a type are only stored.
+    fn docx(body: &str, parts: &[(&str, &str, &str)]) -> Vec<u8> {
+        let document = format!("<w:document {}><w:body>{}</w:body></w:document>", NAMESPACES, body);
+        let mut files = vec![("word/document.xml".to_string(), document)];
+        let mut rels = String::from(r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#);
+        for (i, (kind, target, content)) in parts.iter().enumerate() {
+            if !kind.is_empty() {
+                let mode = if *kind == HYPERLINK { r#" TargetMode="External""# } else { "" };
+                rels.push_str(&format!(
+                    r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/{}" Target="{}"{}/>"#,
+                    i + 1, kind, target, mode,
+                ));
+            }
+            if !content.is_empty() {
+                let path = if kind.is_empty() { target.to_string() } else { word_path(target) };
+                files.push((path, content.to_string()));
+            }
+        }
+        rels.push_str("</Relationships>");
+        files.push(("word/_rels/document.xml.rels".to_string(), rels));
+        xml::zip(&files.iter().map(|(path, content)| (path.as_str(), content.as_str())).collect::<Vec<_>>())
+    }
+
+    fn p(properties: &str, runs: &str) -> String {
+        format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", properties, runs)
+    }
+
+    fn r(text: &str) -> String {
+        format!(r#"<w:r><w:t xml:space="preserve">{}</w:t></w:r>"#, text)
+    }
+
+    #[test]
+    fn headings_lists_tables_links_and_revisions() {
+        let styles = format!(r#"<w:styles {}>
+            <w:style w:type="paragraph" w:styleId="berschrift1"><w:name w:val="heading 1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="Clause"><w:name w:val="Clause"/><w:basedOn w:val="berschrift1"/></w:style>
+            <w:style w:type="paragraph" w:styleId="Steps"><w:name w:val="Steps"/><w:pPr><w:numPr><w:numId w:val="2"/></w:numPr></w:pPr></w:style>
+        </w:styles>"#, NAMESPACES);
+        let numbering = format!(r#"<w:numbering {}>
+            <w:abstractNum w:abstractNumId="0">
+                <w:lvl w:ilvl="0"><w:numFmt w:val="bullet"/></w:lvl>
+                <w:lvl w:ilvl="1"><w:numFmt w:val="lowerLetter"/></w:lvl>
+            </w:abstractNum>
+            <w:abstractNum w:abstractNumId="1"><w:lvl w:ilvl="0"><w:numFmt w:val="decimal"/></w:lvl></w:abstractNum>
+            <w:num w:numId="1"><w:abstractNumId w:val="0"/></w:num>
+            <w:num w:numId="2"><w:abstractNumId w:val="1"/></w:num>
+        </w:numbering>"#, NAMESPACES);
+        let core = r#"<cp:coreProperties xmlns:cp="urn:cp" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <dc:title>Supply contract</dc:title><dc:creator>Legal</dc:creator></cp:coreProperties>"#;
+        let body = [
+            p(r#"<w:pStyle w:val="berschrift1"/>"#, &r("Terms")),
+            p(r#"<w:pStyle w:val="Clause"/>"#, &r("Scope")),
+            p(r#"<w:outlineLvl w:val="2"/>"#, &r("Details")),
+            p("", &format!(
+                r#"{}<w:ins w:author="A"><w:r><w:t>kept</w:t></w:r></w:ins><w:del w:author="A"><w:r><w:delText>dropped</w:delText></w:r></w:del>{}<w:hyperlink r:id="rId4">{}</w:hyperlink>"#,
+                r("Text "), r(" see "), r("the site"),
+            )),
+            p(r#"<w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr>"#, &r("bullet")),
+            p(r#"<w:numPr><w:ilvl w:val="1"/><w:numId w:val="1"/></w:numPr>"#, &r("lettered")),
+            p(r#"<w:pStyle w:val="Steps"/>"#, &r("step")),
+            format!(
+                "<w:tbl><w:tr><w:tc>{}</w:tc><w:tc>{}</w:tc><w:tc>{}</w:tc></w:tr><w:tr><w:tc><w:tcPr><w:gridSpan w:val=\"2\"/></w:tcPr>{}</w:tc><w:tc>{}</w:tc></w:tr></w:tbl>",
+                p("", &r("Item")), p("", &r("Qty")), p("", &r("Price")), p("", &r("Total")), p("", &r("9")),
+            ),
+        ].concat();
+        let bytes = docx(&body, &[
+            ("styles", "styles.xml", &styles),
+            ("numbering", "numbering.xml", &numbering),
+            ("", "docProps/core.xml", core),
+            (HYPERLINK, "https://example.com/terms", ""),
+        ]);
+
+        let document = read(&bytes).unwrap();
+        assert_eq!(document.to_markdown(), "\
+# Supply contract
+
+By Legal
+
+# Terms
+
+# Scope
+
+### Details
+
+Text kept see [the site](https://example.com/terms)
+
+- bullet
+   1. lettered
+1. step
+
+| Item | Qty | Price |
+| --- | --- | --- |
+| Total |  | 9 |");
+    }
+
+    #[test]
+    fn headers_footers_notes_comments_and_text_boxes() {
+        let header = format!("<w:hdr {}>{}</w:hdr>", NAMESPACES, p("", &r("CONFIDENTIAL")));
+        let footer = format!("<w:ftr {}>{}</w:ftr>", NAMESPACES, p("", &r("Page footer")));
+        let footnotes = format!(
+            r#"<w:footnotes {}><w:footnote w:type="separator" w:id="-1">{}</w:footnote><w:footnote w:id="1">{}</w:footnote></w:footnotes>"#,
+            NAMESPACES, p("", "<w:r><w:separator/></w:r>"), p("", &format!("<w:r><w:footnoteRef/></w:r>{}", r(" Per annex B."))),
+        );
+        let endnotes = format!(r#"<w:endnotes {}><w:endnote w:id="1">{}</w:endnote></w:endnotes>"#, NAMESPACES, p("", &r("Sources.")));
+        let comments = format!(r#"<w:comments {}><w:comment w:id="0" w:author="Dana">{}</w:comment></w:comments>"#, NAMESPACES, p("", &r("Check this")));
+        let text_box = format!(
+            "<w:r><mc:AlternateContent><mc:Choice><w:drawing><wps:wsp><wps:txbx><w:txbxContent>{}</w:txbxContent></wps:txbx></wps:wsp></w:drawing></mc:Choice><mc:Fallback><w:pict><v:shape><v:textbox><w:txbxContent>{}</w:txbxContent></v:textbox></v:shape></w:pict></mc:Fallback></mc:AlternateContent></w:r>",
+            p("", &r("Boxed")), p("", &r("Boxed")),
+        );
+        let body = [
+            p("", &format!(
+                r#"{}<w:r><w:footnoteReference w:id="1"/></w:r><w:r><w:endnoteReference w:id="1"/></w:r><w:commentRangeStart w:id="0"/>{}<w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r>{}"#,
+                r("Delivery"), r(" in May"), text_box,
+            )),
+            r#"<w:sectPr><w:headerReference w:type="default" r:id="rId1"/><w:headerReference w:type="first" r:id="rId2"/></w:sectPr>"#.to_string(),
+        ].concat();
+        let bytes = docx(&body, &[
+            ("header", "header1.xml", &header),
+            ("header", "header2.xml", &header),
+            ("footer", "footer1.xml", &footer),
+            ("footnotes", "footnotes.xml", &footnotes),
+            ("endnotes", "endnotes.xml", &endnotes),
+            ("comments", "comments.xml", &comments),
+        ]);
+
+        assert_eq!(read(&bytes).unwrap().to_markdown(), "\
+Header: CONFIDENTIAL
+
+Delivery[^1][^e1] in May[^c0]
+
+Boxed
+
+Footer: Page footer
+
+[^1]: Per annex B.
+
+[^e1]: Sources.
+
+[^c0]: Dana: Check this");
+    }
+
+    #[test]
+    fn headers_keep_their_numeric_order() {
+        let headers: Vec<(String, String)> = (1..=11)
+            .map(|n| (format!("header{}.xml", n), format!("<w:hdr {}>{}</w:hdr>", NAMESPACES, p("", &r(&format!("H{}", n))))))
+            .collect();
+        let parts: Vec<(&str, &str, &str)> = headers.iter().rev()
+            .map(|(target, content)| ("header", target.as_str(), content.as_str()))
+            .collect();
+        let markdown = read(&docx(&p("", &r("Body")), &parts)).unwrap().to_markdown();
+        let expected: Vec<String> = (1..=11).map(|n| format!("Header: H{}", n)).chain(["Body".to_string()]).collect();
+        assert_eq!(markdown, expected.join("\n\n"));
+        assert_eq!(part_number("word/header10.xml"), Some(10));
+        assert_eq!(part_number("word/footnotes.xml"), None);
+    }
+
+    #[test]
+    fn missing_body_is_an_error() {
+        let bytes = xml::zip(&[("word/other.xml", "<other/>")]);
+        assert!(read(&bytes).unwrap_err().contains("word/document.xml"));
+        assert!(read(b"not a zip").is_err());
+    }
+}
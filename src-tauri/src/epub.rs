@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::document::{Block, BlockKind, Document};
+use crate::file_reader;
+use crate::xml::{self, Element};
+
+/// An `.epub` book as a `Document`: its chapters in spine order, each starting with
+/// its title from the table of contents unless the chapter opens with it already.
+pub fn read(bytes: &[u8]) -> Result<Document, String> {
+    let mut archive = xml::open_zip(bytes, "EPUB")?;
+    let container = xml::zip_part(&mut archive, "META-INF/container.xml")?
+        .ok_or("Not an EPUB: META-INF/container.xml is missing")?;
+    let package_path = container.child("rootfiles")
+        .and_then(|rootfiles| rootfiles.child("rootfile"))
+        .and_then(|rootfile| rootfile.attr("full-path"))
+        .ok_or("Not an EPUB: the container names no package")?
+        .to_string();
+    let package = xml::zip_part(&mut archive, &package_path)?
+        .ok_or_else(|| format!("The package {} is missing", package_path))?;
+
+    // Manifest id to (path in the archive, media type, properties)
+    let manifest: HashMap<&str, (String, &str, &str)> = package.child("manifest")
+        .map(|manifest| manifest.children_named("item")
+            .filter_map(|item| Some((
+                item.attr("id")?,
+                (resolve(&package_path, item.attr("href")?), item.attr("media-type").unwrap_or(""), item.attr("properties").unwrap_or("")),
+            )))
+            .collect())
+        .unwrap_or_default();
+    let spine = package.child("spine").ok_or("The package has no spine")?;
+
+    // EPUB 3 names its table of contents in the manifest, EPUB 2 on the spine
+    let mut titles = HashMap::new();
+    if let Some((path, ..)) = manifest.values().find(|(_, _, properties)| properties.split_whitespace().any(|p| p == "nav")) {
+        if let Some(nav) = xml::zip_entry(&mut archive, path)? {
+            titles = nav_titles(path, &String::from_utf8_lossy(&nav));
+        }
+    } else if let Some((path, ..)) = spine.attr("toc").and_then(|id| manifest.get(id)) {
+        if let Some(ncx) = xml::zip_part(&mut archive, path)? {
+            titles = ncx_titles(path, &ncx);
+        }
+    }
+
+    let mut blocks = Vec::new();
+    for itemref in spine.children_named("itemref") {
+        let Some((path, media_type, _)) = itemref.attr("idref").and_then(|id| manifest.get(id)) else { continue };
+        if !media_type.contains("html") {
+            continue;
+        }
+        let Some(chapter) = xml::zip_entry(&mut archive, path)? else { continue };
+        let chapter = file_reader::parse_html(&String::from_utf8_lossy(&chapter));
+        let title = titles.get(path).cloned().or(chapter.metadata.title);
+        let opens_with_title = match (&title, chapter.blocks.first().map(|block| &block.kind)) {
+            (Some(title), Some(BlockKind::Heading { text, .. })) => text.eq_ignore_ascii_case(title),
+            _ => false,
+        };
+        if let Some(text) = title.filter(|_| !opens_with_title) {
+            blocks.push(Block::new(BlockKind::Heading { level: 1, text }));
+        }
+        blocks.extend(chapter.blocks);
+    }
+
+    let mut document = Document::new(blocks);
+    if let Some(metadata) = package.child("metadata") {
+        let field = |name: &str| metadata.child(name).map(|e| e.text().trim().to_string()).filter(|text| !text.is_empty());
+        document.metadata.title = field("title");
+        document.metadata.author = field("creator");
+        document.metadata.description = field("description");
+    }
+    Ok(document)
+}
+
+/// Path in the archive of `href` as written in the file at `base`: relative to its
+/// directory, percent-encoded and without the fragment.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or("");
+    let mut segments: Vec<&str> = base.split('/').collect();
+    // The file itself is not part of the directory
+    segments.pop();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    percent_decode(&segments.join("/"))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Chapter path to title from an EPUB 3 navigation document; the first entry for a
+/// file wins, as later ones point into its sections.
+fn nav_titles(path: &str, nav: &str) -> HashMap<String, String> {
+    use kuchikiki::traits::TendrilSink;
+    let document = kuchikiki::parse_html().one(nav).document_node;
+    let mut titles = HashMap::new();
+    for link in document.select("nav a").into_iter().flatten() {
+        let Some(href) = link.attributes.borrow().get("href").map(str::to_string) else { continue };
+        let title = link.text_contents().split_whitespace().collect::<Vec<_>>().join(" ");
+        if !title.is_empty() {
+            titles.entry(resolve(path, &href)).or_insert(title);
+        }
+    }
+    titles
+}
+
+/// Chapter path to title from an EPUB 2 `toc.ncx`, nested entries included.
+fn ncx_titles(path: &str, ncx: &Element) -> HashMap<String, String> {
+    fn walk(path: &str, parent: &Element, titles: &mut HashMap<String, String>) {
+        for point in parent.children_named("navPoint") {
+            let title = point.child("navLabel").and_then(|label| label.child("text")).map(|text| text.text());
+            let src = point.child("content").and_then(|content| content.attr("src"));
+            if let (Some(title), Some(src)) = (title, src) {
+                let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !title.is_empty() {
+                    titles.entry(resolve(path, src)).or_insert(title);
+                }
+            }
+            walk(path, point, titles);
+        }
+    }
+    let mut titles = HashMap::new();
+    if let Some(map) = ncx.child("navMap") {
+        walk(path, map, &mut titles);
+    }
+    titles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An `.epub` with its package at `OEBPS/content.opf` and `files` under `OEBPS/`.
+    fn epub(package: &str, files: &[(&str, &str)]) -> Vec<u8> {
+        let container = r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+            <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let mut entries = vec![
+            ("mimetype".to_string(), "application/epub+zip"),
+            ("META-INF/container.xml".to_string(), container),
+            ("OEBPS/content.opf".to_string(), package),
+        ];
+        entries.extend(files.iter().map(|(path, content)| (format!("OEBPS/{}", path), *content)));
+        xml::zip(&entries.iter().map(|(path, content)| (path.as_str(), *content)).collect::<Vec<_>>())
+    }
+
+    fn chapter(body: &str) -> String {
+        format!(r#"<?xml version="1.0"?><html xmlns="http://www.w3.org/1999/xhtml"><head><title>Ignored</title></head><body>{}</body></html>"#, body)
+    }
+
+    #[test]
+    fn chapters_follow_the_spine_with_their_titles() {
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf" xmlns:dc="http://purl.org/dc/elements/1.1/" version="3.0">
+            <metadata><dc:title>On Rivers</dc:title><dc:creator>A. Author</dc:creator></metadata>
+            <manifest>
+                <item id="two" href="text/chapter%202.xhtml" media-type="application/xhtml+xml"/>
+                <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+                <item id="one" href="text/one.xhtml" media-type="application/xhtml+xml"/>
+                <item id="cover" href="cover.jpg" media-type="image/jpeg"/>
+            </manifest>
+            <spine><itemref idref="cover"/><itemref idref="one"/><itemref idref="two"/></spine>
+        </package>"#;
+        let nav = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body><nav epub:type="toc"><ol>
+            <li><a href="text/one.xhtml">Sources</a><ol><li><a href="text/one.xhtml#springs">Springs</a></li></ol></li>
+            <li><a href="text/chapter%202.xhtml">Deltas</a></li>
+        </ol></nav></body></html>"#;
+        let bytes = epub(package, &[
+            ("nav.xhtml", nav),
+            ("text/one.xhtml", &chapter("<p>Water starts &amp; flows.</p>")),
+            ("text/chapter 2.xhtml", &chapter("<h1>Deltas</h1><p>It ends.</p>")),
+        ]);
+
+        let document = read(&bytes).unwrap();
+        assert_eq!(document.to_markdown(), "# On Rivers\n\nBy A. Author\n\n# Sources\n\nWater starts & flows.\n\n# Deltas\n\nIt ends.");
+    }
+
+    #[test]
+    fn epub2_titles_come_from_the_ncx() {
+        let package = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0"><metadata/>
+            <manifest>
+                <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                <item id="a" href="a.html" media-type="application/xhtml+xml"/>
+                <item id="b" href="b.html" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine toc="ncx"><itemref idref="a"/><itemref idref="b"/></spine>
+        </package>"#;
+        let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/"><navMap>
+            <navPoint id="p1"><navLabel><text>Part One</text></navLabel><content src="a.html"/>
+                <navPoint id="p2"><navLabel><text>Later</text></navLabel><content src="b.html"/></navPoint>
+            </navPoint>
+        </navMap></ncx>"#;
+        let bytes = epub(package, &[("toc.ncx", ncx), ("a.html", &chapter("<p>a</p>")), ("b.html", &chapter("<p>b</p>"))]);
+        assert_eq!(read(&bytes).unwrap().to_markdown(), "# Part One\n\na\n\n# Later\n\nb");
+    }
+
+    #[test]
+    fn paths_resolve_against_the_referring_file() {
+        assert_eq!(resolve("OEBPS/content.opf", "text/ch%201.xhtml#top"), "OEBPS/text/ch 1.xhtml");
+        assert_eq!(resolve("OEBPS/nav/toc.xhtml", "../text/a.xhtml"), "OEBPS/text/a.xhtml");
+        assert_eq!(resolve("content.opf", "a.xhtml"), "a.xhtml");
+    }
+}
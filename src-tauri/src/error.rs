@@ -0,0 +1,166 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+use crate::protocol::RpcError;
+
+/// What went wrong, for commands and the agent client alike. Serialized as
+/// `{"kind": "...", ...}` so the UI can react to the kind instead of parsing messages.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum AppError {
+    /// No agent binary at the configured path, or none configured and none installed
+    CliNotFound { path: Option<String> },
+    SpawnFailed { program: String, message: String },
+    /// Checking the agent binary failed with `cause`; `stderr` is what it printed last
+    CliCheckFailed { cause: Box<AppError>, stderr: Vec<String> },
+    /// No agent profile with this id in the settings
+    AgentNotFound { agent_id: String },
+    /// The agent answered a request with a JSON-RPC error
+    ProtocolError { code: i64, message: String, data: Option<Value> },
+    /// The agent's response did not have the shape `method` promises
+    InvalidResponse { method: String, message: String },
+    /// A live process has the session open; `pid` and `started_at` when its lock says so
+    SessionLocked { session_id: String, pid: Option<u32>, started_at: Option<String> },
+    SessionNotFound { session_id: String },
+    /// The session's agent was stopped to save resources; the next prompt starts it again
+    SessionSuspended { session_id: String },
+    /// The client has not created or loaded a session yet
+    NoActiveSession,
+    PromptRunning { session_id: String },
+    NoInterruptedPrompt { session_id: String },
+    PermissionNotPending,
+    /// A session id that is not a plain file name, so it cannot name a session's files
+    InvalidSessionId { session_id: String },
+    /// No response within the configured time; the agent may be wedged
+    Timeout { method: String, seconds: u64 },
+    /// The agent process went away while we waited on it
+    ProcessClosed,
+    NoWorkspace,
+    /// The user's home or config directory, named by `name`, could not be found
+    NoUserDirectory { name: String },
+    FileNotFound { path: String },
+    /// The path resolves to somewhere outside the workspace
+    AccessDenied { path: String },
+    /// An agent asked to write somewhere other than the notes directory
+    OutsideNotes { path: String, notes: String },
+    UnsupportedFileType { extension: String },
+    /// `pages` is 0 for files without pages
+    PageOutOfRange { from: u32, to: u32, pages: u32 },
+    /// A tool was called with an argument it cannot use
+    InvalidArgument { name: String, message: String },
+    UnknownTool { name: String },
+    /// The file exists but its text could not be read or extracted
+    ExtractionFailed { path: String, message: String },
+    /// A file the app keeps for itself, such as the settings or a session lock, could
+    /// not be read, written or parsed
+    Io { path: String, message: String },
+    /// A bug rather than something the user can act on, such as a task that panicked
+    Internal { message: String },
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CliNotFound { path: Some(path) } => write!(f, "kiro-cli not found at {} (set in settings)", path),
+            Self::CliNotFound { path: None } => f.write_str("kiro-cli not found. Please install Kiro CLI."),
+            Self::SpawnFailed { program, message } => write!(f, "Failed to spawn {}: {}", program, message),
+            Self::CliCheckFailed { cause, stderr } if stderr.is_empty() => write!(f, "{}", cause),
+            Self::CliCheckFailed { cause, stderr } => write!(f, "{}\n{}", cause, stderr.join("\n")),
+            Self::AgentNotFound { agent_id } => write!(f, "Unknown agent: {}", agent_id),
+            Self::ProtocolError { code, message, .. } => write!(f, "ACP error: {} (code {})", message, code),
+            Self::InvalidResponse { method, message } => write!(f, "Invalid {} response: {}", method, message),
+            Self::SessionLocked { session_id, pid: Some(pid), .. } => {
+                write!(f, "Session {} is active in another process (PID {})", session_id, pid)
+            }
+            Self::SessionLocked { session_id, pid: None, .. } => write!(f, "Session {} is active in another process", session_id),
+            Self::SessionNotFound { session_id } => write!(f, "Session not found: {}", session_id),
+            Self::SessionSuspended { session_id } => {
+                write!(f, "Session {} is suspended; its agent is started again on the next prompt", session_id)
+            }
+            Self::NoActiveSession => f.write_str("No active session"),
+            Self::PromptRunning { session_id } => write!(f, "A prompt is already running in session {}", session_id),
+            Self::NoInterruptedPrompt { session_id } => write!(f, "No interrupted prompt for session {}", session_id),
+            Self::PermissionNotPending => f.write_str("Permission request is no longer pending"),
+            Self::InvalidSessionId { session_id } => write!(f, "Invalid session id: {:?}", session_id),
+            Self::Timeout { method, seconds } => write!(f, "{} timed out after {}s", method, seconds),
+            Self::ProcessClosed => f.write_str("ACP process closed"),
+            Self::NoWorkspace => f.write_str("No workspace selected"),
+            Self::NoUserDirectory { name } => write!(f, "Cannot find the {} directory", name),
+            Self::FileNotFound { path } => write!(f, "File not found: {}", path),
+            Self::AccessDenied { path } => write!(f, "Access denied: {} is outside the workspace", path),
+            Self::OutsideNotes { path, notes } => write!(f, "Writes are limited to {}: {}", notes, path),
+            Self::UnsupportedFileType { extension } => write!(f, "Unsupported file type: {}", extension),
+            Self::PageOutOfRange { pages: 0, .. } => f.write_str("This file has no pages"),
+            Self::PageOutOfRange { from, to, pages } => {
+                write!(f, "Pages {}-{} are out of range; the file has {} pages", from, to, pages)
+            }
+            Self::InvalidArgument { name, message } => write!(f, "Invalid `{}`: {}", name, message),
+            Self::UnknownTool { name } => write!(f, "Unknown tool: {}", name),
+            Self::ExtractionFailed { path, message } => write!(f, "Failed to read {}: {}", path, message),
+            Self::Io { path, message } => write!(f, "{}: {}", path, message),
+            Self::Internal { message } => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl AppError {
+    pub fn io(path: &Path, error: impl fmt::Display) -> Self {
+        Self::Io { path: path.display().to_string(), message: error.to_string() }
+    }
+
+    /// `FileNotFound` when `path` is missing, `Io` for anything else.
+    pub fn from_io(path: &Path, error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::FileNotFound { path: path.display().to_string() },
+            _ => Self::io(path, error),
+        }
+    }
+
+    pub fn internal(error: impl fmt::Display) -> Self {
+        Self::Internal { message: error.to_string() }
+    }
+}
+
+impl From<RpcError> for AppError {
+    fn from(error: RpcError) -> Self {
+        Self::ProtocolError { code: error.code, message: error.message, data: error.data }
+    }
+}
+
+impl From<AppError> for String {
+    fn from(error: AppError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_with_its_kind() {
+        let locked = AppError::SessionLocked { session_id: "s1".to_string(), pid: Some(42), started_at: None };
+        assert_eq!(
+            serde_json::to_value(&locked).unwrap(),
+            json!({ "kind": "sessionLocked", "sessionId": "s1", "pid": 42, "startedAt": null }),
+        );
+        assert_eq!(serde_json::to_value(AppError::ProcessClosed).unwrap(), json!({ "kind": "processClosed" }));
+        let check = AppError::CliCheckFailed { cause: Box::new(AppError::ProcessClosed), stderr: vec!["boom".to_string()] };
+        assert_eq!(
+            serde_json::to_value(&check).unwrap(),
+            json!({ "kind": "cliCheckFailed", "cause": { "kind": "processClosed" }, "stderr": ["boom"] }),
+        );
+    }
+
+    #[test]
+    fn rpc_errors_keep_code_and_data() {
+        let error = AppError::from(RpcError { code: -32603, message: "boom".to_string(), data: Some(json!({ "detail": 1 })) });
+        assert_eq!(error, AppError::ProtocolError { code: -32603, message: "boom".to_string(), data: Some(json!({ "detail": 1 })) });
+        assert_eq!(error.to_string(), "ACP error: boom (code -32603)");
+    }
+}
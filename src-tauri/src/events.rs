@@ -0,0 +1,198 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::protocol::{
+    self, ContentBlock, PlanEntry, SessionUpdate, ToolCallLocation, ToolCallStatus, ToolKind,
+};
+
+/// `acp-chunk` (agent text), `acp-thought` (agent reasoning) and `acp-user-chunk`
+/// (user text replayed by `session/load`).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextChunk {
+    pub session_id: String,
+    pub text: String,
+}
+
+/// `acp-plan`: the agent's current plan. Every update carries the full list.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanEvent {
+    pub session_id: String,
+    pub entries: Vec<PlanEntry>,
+}
+
+/// `acp-tool-call` for a new call and `acp-tool-call-update` for later changes to it.
+/// Updates only carry the fields that changed.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallEvent {
+    pub session_id: String,
+    pub tool_call_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ToolKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ToolCallStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locations: Option<Vec<ToolCallLocation>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<ToolCallContent>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum ToolCallContent {
+    Text { text: String },
+    Diff { path: String, old_text: Option<String>, new_text: String },
+    Terminal { terminal_id: String },
+    /// Non-text content blocks (images, resources) are passed through as-is
+    Block { block: ContentBlock },
+}
+
+/// `acp-commands`: slash commands the agent currently accepts.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandsEvent {
+    pub session_id: String,
+    pub commands: Vec<CommandInfo>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandInfo {
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_hint: Option<String>,
+}
+
+/// `acp-mode`: the agent switched modes.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeEvent {
+    pub session_id: String,
+    pub mode_id: String,
+}
+
+/// `acp-stalled`: a prompt has been running for `idle_seconds` without any update.
+/// Sent again each time the inactivity timeout passes; the next update or the end
+/// of the turn means it is moving again.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StalledEvent {
+    pub session_id: String,
+    pub idle_seconds: u64,
+}
+
+/// `acp-crashed`: the agent process behind a session exited on its own.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashedEvent {
+    pub session_id: String,
+    pub exit_status: Option<String>,
+    /// Last lines the agent wrote to stderr
+    pub stderr: Vec<String>,
+    /// Protocol trace of the dead process, for bug reports
+    pub trace_path: Option<String>,
+    /// A prompt was running and can be sent again with `retry_prompt`
+    pub interrupted_prompt: bool,
+}
+
+/// `acp-restarted` once the session is reloaded in a new process, or
+/// `acp-restart-failed` with the `error` when that did not work.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RestartEvent {
+    pub session_id: String,
+    pub interrupted_prompt: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+/// Maps a `session/update` payload to the Tauri event it is emitted as.
+/// Returns `None` for updates the UI has no use for.
+pub fn from_update(session_id: &str, update: &SessionUpdate) -> Option<(&'static str, Value)> {
+    let session_id = session_id.to_string();
+    let (name, payload) = match update {
+        SessionUpdate::AgentMessageChunk { content } => {
+            ("acp-chunk", serde_json::to_value(TextChunk { session_id, text: content.as_text()?.to_string() }))
+        }
+        SessionUpdate::AgentThoughtChunk { content } => {
+            ("acp-thought", serde_json::to_value(TextChunk { session_id, text: content.as_text()?.to_string() }))
+        }
+        SessionUpdate::UserMessageChunk { content } => {
+            ("acp-user-chunk", serde_json::to_value(TextChunk { session_id, text: content.as_text()?.to_string() }))
+        }
+        SessionUpdate::Plan(plan) => {
+            ("acp-plan", serde_json::to_value(PlanEvent { session_id, entries: plan.entries.clone() }))
+        }
+        SessionUpdate::ToolCall(call) => {
+            let event = ToolCallEvent {
+                session_id,
+                tool_call_id: call.tool_call_id.clone(),
+                // A new call always has a title; fill in a placeholder when it is blank
+                title: Some(if call.title.is_empty() { "Working".to_string() } else { call.title.clone() }),
+                kind: Some(call.kind),
+                status: Some(call.status),
+                locations: (!call.locations.is_empty()).then(|| call.locations.clone()),
+                raw_input: call.raw_input.clone(),
+                raw_output: call.raw_output.clone(),
+                content: (!call.content.is_empty()).then(|| call.content.iter().map(tool_call_content).collect()),
+            };
+            ("acp-tool-call", serde_json::to_value(event))
+        }
+        SessionUpdate::ToolCallUpdate(update) => {
+            let event = ToolCallEvent {
+                session_id,
+                tool_call_id: update.tool_call_id.clone(),
+                title: update.title.clone(),
+                kind: update.kind,
+                status: update.status,
+                locations: update.locations.clone(),
+                raw_input: update.raw_input.clone(),
+                raw_output: update.raw_output.clone(),
+                content: update.content.as_ref().map(|content| content.iter().map(tool_call_content).collect()),
+            };
+            ("acp-tool-call-update", serde_json::to_value(event))
+        }
+        SessionUpdate::AvailableCommandsUpdate { available_commands } => {
+            let commands = available_commands.iter()
+                .map(|c| CommandInfo {
+                    name: c.name.clone(),
+                    description: c.description.clone(),
+                    input_hint: c.input.as_ref().map(|input| input.hint.clone()),
+                })
+                .collect();
+            ("acp-commands", serde_json::to_value(CommandsEvent { session_id, commands }))
+        }
+        SessionUpdate::CurrentModeUpdate { current_mode_id } => {
+            ("acp-mode", serde_json::to_value(ModeEvent { session_id, mode_id: current_mode_id.clone() }))
+        }
+        SessionUpdate::Unknown => return None,
+    };
+    Some((name, payload.ok()?))
+}
+
+fn tool_call_content(item: &protocol::ToolCallContent) -> ToolCallContent {
+    match item {
+        protocol::ToolCallContent::Content { content: ContentBlock::Text(text) } => {
+            ToolCallContent::Text { text: text.text.clone() }
+        }
+        protocol::ToolCallContent::Content { content } => ToolCallContent::Block { block: content.clone() },
+        protocol::ToolCallContent::Diff { path, old_text, new_text } => ToolCallContent::Diff {
+            path: path.clone(),
+            old_text: old_text.clone(),
+            new_text: new_text.clone(),
+        },
+        protocol::ToolCallContent::Terminal { terminal_id } => {
+            ToolCallContent::Terminal { terminal_id: terminal_id.clone() }
+        }
+    }
+}
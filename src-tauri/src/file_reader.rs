@@ -1,26 +1,55 @@
+use kuchikiki::traits::TendrilSink;
+use kuchikiki::NodeRef;
 use std::fs;
 use std::path::Path;
 
-pub fn read_file(path: &str) -> Result<String, String> {
-    let path = Path::new(path);
-    let ext = path
-        .extension()
+use crate::docx;
+use crate::epub;
+use crate::document::{self, Block, BlockKind, Document};
+use crate::error::AppError;
+
+/// Whether the file is in a binary format only `read_file` gets text out of;
+/// the others can be read as they are.
+pub fn needs_extraction(path: &Path) -> bool {
+    matches!(extension(path).as_str(), "pdf" | "docx" | "epub")
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
         .and_then(|e| e.to_str())
         .unwrap_or("")
-        .to_lowercase();
+        .to_lowercase()
+}
 
+/// Markdown and plain text files as they are; the others as Markdown, see
+/// `Document::to_markdown`.
+pub fn read_file(path: &str) -> Result<String, AppError> {
+    let file = Path::new(path);
+    match extension(file).as_str() {
+        "md" | "txt" => fs::read_to_string(file)
+            .map_err(|e| AppError::ExtractionFailed { path: file.display().to_string(), message: e.to_string() }),
+        _ => read_document(path).map(|document| document.to_markdown()),
+    }
+}
+
+pub fn read_document(path: &str) -> Result<Document, AppError> {
+    let path = Path::new(path);
+    let ext = extension(path);
+    let failed = |message: String| AppError::ExtractionFailed { path: path.display().to_string(), message };
+    let text = || fs::read_to_string(path).map_err(|e| failed(e.to_string()));
     match ext.as_str() {
-        "pdf" => read_pdf(path),
-        "docx" => read_docx(path),
-        "md" | "txt" | "html" => {
-            fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))
-        }
-        _ => Err(format!("Unsupported file type: {}", ext)),
+        "pdf" => read_pdf(path).map_err(failed),
+        "docx" => read_docx(path).map_err(failed),
+        "epub" => read_epub(path).map_err(failed),
+        "md" => Ok(parse_markdown(&text()?)),
+        "txt" => Ok(parse_plain_text(&text()?)),
+        "html" | "htm" | "xhtml" => Ok(parse_html(&text()?)),
+        _ => Err(AppError::UnsupportedFileType { extension: ext }),
     }
 }
 
-fn read_pdf(path: &Path) -> Result<String, String> {
-    // 使用 macOS 原生 PDFKit 提取文本，兼容性最好
+fn read_pdf(path: &Path) -> Result<Document, String> {
+    // 使用 macOS 原生 PDFKit 提取文本，兼容性最好；每页以换页符结束
     #[cfg(target_os = "macos")]
     {
         let output = std::process::Command::new("swift")
@@ -30,65 +59,703 @@ fn read_pdf(path: &Path) -> Result<String, String> {
 if let doc = PDFDocument(url: URL(fileURLWithPath: {:?})) {{
     var text = ""
     for i in 0..<doc.pageCount {{
-        if let page = doc.page(at: i), let s = page.string {{
-            text += s + "\n"
-        }}
+        text += (doc.page(at: i)?.string ?? "") + "\u{{000C}}"
     }}
-    print(text)
+    print(text, terminator: "")
 }}"#,
                 path.to_str().unwrap_or("")
             ))
             .output()
-            .map_err(|e| format!("Failed to run PDF extractor: {}", e))?;
+            .map_err(|e| format!("could not run the PDF extractor: {}", e))?;
 
         if output.status.success() {
             let text = String::from_utf8_lossy(&output.stdout).to_string();
-            if !text.trim().is_empty() {
-                return Ok(text);
+            if !text.trim_matches(|c: char| c.is_whitespace() || c == '\u{0C}').is_empty() {
+                return Ok(paged(text.split_terminator('\u{0C}').map(str::to_string)));
             }
         }
     }
 
     // 非 macOS 或 PDFKit 失败时，回退到 lopdf 逐页提取
-    let bytes = fs::read(path).map_err(|e| format!("Failed to read PDF: {}", e))?;
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
     let doc = lopdf::Document::load_mem(&bytes)
-        .map_err(|e| format!("Failed to parse PDF: {}", e))?;
-    let pages = doc.get_pages();
-    let mut page_numbers: Vec<u32> = pages.keys().cloned().collect();
+        .map_err(|e| format!("not a valid PDF: {}", e))?;
+    let mut page_numbers: Vec<u32> = doc.get_pages().keys().cloned().collect();
     page_numbers.sort();
 
-    let mut text = String::new();
-    for &pn in &page_numbers {
-        if let Ok(page_text) = doc.extract_text(&[pn]) {
-            text.push_str(&page_text);
+    // A page that yields nothing keeps its place, so numbering matches the viewer
+    let pages: Vec<String> = page_numbers
+        .iter()
+        .map(|&number| doc.extract_text(&[number]).unwrap_or_default())
+        .collect();
+
+    if pages.iter().all(|page| page.trim().is_empty()) {
+        Err("no text found in the PDF".to_string())
+    } else {
+        Ok(paged(pages.into_iter()))
+    }
+}
+
+/// One page break per page, followed by its paragraphs.
+fn paged(pages: impl Iterator<Item = String>) -> Document {
+    let mut blocks = Vec::new();
+    for (text, number) in pages.zip(1..) {
+        blocks.push(Block::new(BlockKind::PageBreak { number }));
+        blocks.extend(paragraphs(&text).into_iter().map(|(text, _)| Block::new(BlockKind::Paragraph { text })));
+    }
+    Document::new(blocks)
+}
+
+fn read_docx(path: &Path) -> Result<Document, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    docx::read(&bytes)
+}
+
+fn read_epub(path: &Path) -> Result<Document, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    epub::read(&bytes)
+}
+
+/// Paragraphs of `text` separated by blank lines, with their byte ranges.
+fn paragraphs(text: &str) -> Vec<(String, std::ops::Range<usize>)> {
+    let mut paragraphs = Vec::new();
+    let mut current: Option<std::ops::Range<usize>> = None;
+    for (start, line) in lines(text) {
+        if line.trim().is_empty() {
+            if let Some(range) = current.take() {
+                paragraphs.push((text[range.clone()].to_string(), range));
+            }
+        } else {
+            let end = start + line.trim_end().len();
+            current = Some(current.map_or(start..end, |range| range.start..end));
         }
     }
+    if let Some(range) = current {
+        paragraphs.push((text[range.clone()].to_string(), range));
+    }
+    paragraphs
+}
 
-    if text.is_empty() {
-        Err("Failed to extract any text from PDF".to_string())
+/// Lines of `text` with the byte offset each starts at, without their line ending.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.trim_end_matches(['\n', '\r'])))
+    })
+}
+
+fn parse_plain_text(text: &str) -> Document {
+    Document::new(paragraphs(text).into_iter().map(|(text, range)| Block::at(BlockKind::Paragraph { text }, range)).collect())
+}
+
+/// Block structure of CommonMark-style Markdown: ATX and setext headings, fenced code,
+/// lists, pipe tables, block quotes and paragraphs. Inline markup is kept as written.
+fn parse_markdown(text: &str) -> Document {
+    let lines: Vec<(usize, &str)> = lines(text).collect();
+    let end_of = |i: usize| lines[i].0 + lines[i].1.len();
+    let mut blocks = Vec::new();
+    let mut paragraph: Option<(usize, Vec<&str>)> = None;
+    let flush = |paragraph: &mut Option<(usize, Vec<&str>)>, blocks: &mut Vec<Block>, end: usize| {
+        if let Some((first, texts)) = paragraph.take() {
+            blocks.push(Block::at(BlockKind::Paragraph { text: texts.join("\n") }, lines[first].0..end));
+        }
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (start, line) = lines[i];
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let previous_end = if i > 0 { end_of(i - 1) } else { 0 };
+
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &mut blocks, previous_end);
+            i += 1;
+            continue;
+        }
+
+        // Setext heading: a paragraph underlined with `=` or `-`
+        if let Some((first, texts)) = &paragraph {
+            let underline = trimmed.trim_end();
+            if indent < 4 && !underline.is_empty() && (underline.chars().all(|c| c == '=') || underline.chars().all(|c| c == '-')) {
+                let level = if underline.starts_with('=') { 1 } else { 2 };
+                blocks.push(Block::at(BlockKind::Heading { level, text: texts.join(" ") }, lines[*first].0..end_of(i)));
+                paragraph = None;
+                i += 1;
+                continue;
+            }
+        }
+
+        if indent < 4 {
+            if let Some((fence, language)) = code_fence(trimmed) {
+                flush(&mut paragraph, &mut blocks, previous_end);
+                let mut code = Vec::new();
+                let mut j = i + 1;
+                while j < lines.len() && !closes_fence(lines[j].1, &fence) {
+                    code.push(lines[j].1);
+                    j += 1;
+                }
+                let last = j.min(lines.len() - 1);
+                blocks.push(Block::at(BlockKind::CodeBlock { language, code: code.join("\n") }, start..end_of(last)));
+                i = j + 1;
+                continue;
+            }
+
+            if let Some((level, heading)) = atx_heading(trimmed) {
+                flush(&mut paragraph, &mut blocks, previous_end);
+                blocks.push(Block::at(BlockKind::Heading { level, text: heading }, start..end_of(i)));
+                i += 1;
+                continue;
+            }
+
+            if trimmed.starts_with('>') {
+                flush(&mut paragraph, &mut blocks, previous_end);
+                let mut quoted = Vec::new();
+                let mut j = i;
+                while j < lines.len() {
+                    let Some(rest) = lines[j].1.trim_start().strip_prefix('>') else { break };
+                    quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                    j += 1;
+                }
+                blocks.push(Block::at(BlockKind::Quote { text: quoted.join("\n") }, start..end_of(j - 1)));
+                i = j;
+                continue;
+            }
+
+            if trimmed.contains('|') && i + 1 < lines.len() && is_table_separator(lines[i + 1].1) {
+                flush(&mut paragraph, &mut blocks, previous_end);
+                let mut rows = vec![table_cells(trimmed)];
+                let mut j = i + 2;
+                while j < lines.len() && lines[j].1.contains('|') && !lines[j].1.trim().is_empty() {
+                    rows.push(table_cells(lines[j].1.trim()));
+                    j += 1;
+                }
+                blocks.push(Block::at(BlockKind::Table { rows }, start..end_of(j - 1)));
+                i = j;
+                continue;
+            }
+        }
+
+        if let Some((ordered, marker_width, item)) = list_marker(trimmed) {
+            // Items interrupt paragraphs; nesting follows the indentation
+            flush(&mut paragraph, &mut blocks, previous_end);
+            let content_indent = indent + marker_width;
+            let mut texts = vec![item];
+            let mut j = i + 1;
+            // Lazy continuation lines belong to the item
+            while j < lines.len() {
+                let next = lines[j].1;
+                let next_trimmed = next.trim_start();
+                let next_indent = next.len() - next_trimmed.len();
+                if next_trimmed.is_empty() || list_marker(next_trimmed).is_some() || next_indent < content_indent && starts_block(next_trimmed) {
+                    break;
+                }
+                texts.push(next_trimmed);
+                j += 1;
+            }
+            let depth = indent / 2;
+            blocks.push(Block::at(BlockKind::ListItem { ordered, depth, text: texts.join("\n") }, start..end_of(j - 1)));
+            i = j;
+            continue;
+        }
+
+        match &mut paragraph {
+            Some((_, texts)) => texts.push(trimmed),
+            None => paragraph = Some((i, vec![trimmed])),
+        }
+        i += 1;
+    }
+    flush(&mut paragraph, &mut blocks, lines.last().map(|(start, line)| start + line.len()).unwrap_or(0));
+    Document::new(blocks)
+}
+
+/// The fence and info string of a line opening a fenced code block
+fn code_fence(line: &str) -> Option<(String, Option<String>)> {
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let fence: String = line.chars().take_while(|c| *c == fence_char).collect();
+    if fence.len() < 3 {
+        return None;
+    }
+    let info = line[fence.len()..].trim();
+    if fence_char == '`' && info.contains('`') {
+        return None;
+    }
+    Some((fence, info.split_whitespace().next().map(str::to_string)))
+}
+
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim();
+    let fence_char = fence.chars().next().unwrap_or('`');
+    trimmed.len() >= fence.len() && trimmed.chars().all(|c| c == fence_char)
+}
+
+fn atx_heading(line: &str) -> Option<(u8, String)> {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[hashes..];
+    if !(1..=6).contains(&hashes) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    // An optional closing sequence of `#` is not part of the text
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some((hashes as u8, text.to_string()))
+}
+
+/// `(ordered, width of the marker and its space, text)` for a list item line
+fn list_marker(line: &str) -> Option<(bool, usize, &str)> {
+    let (ordered, marker_len) = if line.starts_with(['-', '*', '+']) {
+        (false, 1)
     } else {
-        Ok(text)
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 || digits > 9 || !line[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        (true, digits + 1)
+    };
+    let rest = &line[marker_len..];
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    // `---` and `* * *` are rules, not items
+    if !ordered && rest.trim().chars().all(|c| c == line.as_bytes()[0] as char || c == ' ') && !rest.trim().is_empty() {
+        return None;
+    }
+    let text = rest.trim_start();
+    Some((ordered, line.len() - text.len(), text.trim_end()))
+}
+
+/// Whether a line starts a block other than a paragraph continuation
+fn starts_block(line: &str) -> bool {
+    line.starts_with('>') || atx_heading(line).is_some() || code_fence(line).is_some()
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let cells = table_cells(line.trim());
+    !cells.is_empty() && cells.iter().all(|cell| {
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+    })
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('|') => cell.push('|'),
+                Some(other) => {
+                    cell.push('\\');
+                    cell.push(other);
+                }
+                None => cell.push('\\'),
+            },
+            '|' => cells.push(std::mem::take(&mut cell).trim().to_string()),
+            _ => cell.push(c),
+        }
     }
+    cells.push(cell.trim().to_string());
+    cells
 }
 
-fn read_docx(path: &Path) -> Result<String, String> {
-    let bytes = fs::read(path).map_err(|e| format!("Failed to read DOCX: {}", e))?;
-    let doc = docx_rs::read_docx(&bytes).map_err(|e| format!("Failed to parse DOCX: {}", e))?;
+/// Elements that start a block of their own; anything else is inline text
+const HTML_CONTAINERS: &[&str] = &[
+    "html", "body", "div", "section", "article", "main", "header", "footer", "nav", "aside",
+    "figure", "figcaption", "form", "fieldset", "details", "summary", "dl", "dd", "dt", "address", "center",
+];
+
+/// Elements whose content is never shown as text
+const HTML_HIDDEN: &[&str] = &["script", "style", "noscript", "template", "head"];
+
+pub fn parse_html(html: &str) -> Document {
+    let document = kuchikiki::parse_html().one(html).document_node;
+    let mut blocks = Vec::new();
+    if let Ok(body) = document.select_first("body") {
+        html_blocks(body.as_node(), &mut blocks);
+    }
+    let mut parsed = Document::new(blocks);
+    parsed.metadata.title = document.select_first("title").ok()
+        .map(|title| collapse_whitespace(&source_text(&title.text_contents())))
+        .filter(|title| !title.is_empty());
+    parsed.metadata.author = html_meta(&document, "author");
+    parsed.metadata.description = html_meta(&document, "description");
+    parsed
+}
+
+/// `content` of `<meta name="{name}">`
+fn html_meta(document: &NodeRef, name: &str) -> Option<String> {
+    document.select("meta").ok()?.find_map(|meta| {
+        let attributes = meta.attributes.borrow();
+        if !attributes.get("name")?.eq_ignore_ascii_case(name) {
+            return None;
+        }
+        Some(collapse_whitespace(&source_text(attributes.get("content")?))).filter(|content| !content.is_empty())
+    })
+}
+
+fn html_blocks(node: &NodeRef, blocks: &mut Vec<Block>) {
+    let mut inline = String::new();
+    let flush = |inline: &mut String, blocks: &mut Vec<Block>| {
+        let text = collapse_whitespace(&std::mem::take(inline));
+        if !text.is_empty() {
+            blocks.push(Block::new(BlockKind::Paragraph { text }));
+        }
+    };
+    for child in node.children() {
+        let Some(element) = child.as_element() else {
+            if let Some(text) = child.as_text() {
+                inline.push_str(&source_text(&text.borrow()));
+            }
+            continue;
+        };
+        let name = element.name.local.to_string();
+        match name.as_str() {
+            _ if HTML_HIDDEN.contains(&name.as_str()) => {}
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                flush(&mut inline, blocks);
+                let text = collapse_whitespace(&inline_text(&child));
+                if !text.is_empty() {
+                    let level = name[1..].parse().unwrap_or(1);
+                    blocks.push(Block::new(BlockKind::Heading { level, text }));
+                }
+            }
+            "p" => {
+                flush(&mut inline, blocks);
+                inline = inline_text(&child);
+                flush(&mut inline, blocks);
+            }
+            "ul" | "ol" => {
+                flush(&mut inline, blocks);
+                html_list(&child, name == "ol", 0, blocks);
+            }
+            "table" => {
+                flush(&mut inline, blocks);
+                let rows: Vec<Vec<String>> = child.select("tr").into_iter().flatten()
+                    .map(|row| {
+                        row.as_node().children()
+                            .filter(|cell| cell.as_element().is_some_and(|e| matches!(&*e.name.local, "td" | "th")))
+                            .map(|cell| collapse_whitespace(&inline_text(&cell)))
+                            .collect::<Vec<String>>()
+                    })
+                    .filter(|cells| !cells.is_empty())
+                    .collect();
+                if !rows.is_empty() {
+                    blocks.push(Block::new(BlockKind::Table { rows }));
+                }
+            }
+            "pre" => {
+                flush(&mut inline, blocks);
+                let language = child.select_first("code").ok().and_then(|code| {
+                    let class = code.attributes.borrow().get("class")?.to_string();
+                    class.split_whitespace()
+                        .find_map(|c| c.strip_prefix("language-").or_else(|| c.strip_prefix("lang-")))
+                        .map(str::to_string)
+                });
+                let code = child.text_contents();
+                blocks.push(Block::new(BlockKind::CodeBlock { language, code: code.trim_matches('\n').to_string() }));
+            }
+            "blockquote" => {
+                flush(&mut inline, blocks);
+                let mut quoted = Vec::new();
+                html_blocks(&child, &mut quoted);
+                let text = Document::new(quoted).to_markdown();
+                if !text.is_empty() {
+                    blocks.push(Block::new(BlockKind::Quote { text }));
+                }
+            }
+            "br" => inline.push('\n'),
+            "hr" => flush(&mut inline, blocks),
+            _ if HTML_CONTAINERS.contains(&name.as_str()) => {
+                flush(&mut inline, blocks);
+                html_blocks(&child, blocks);
+            }
+            _ => inline.push_str(&inline_text(&child)),
+        }
+    }
+    flush(&mut inline, blocks);
+}
+
+/// Items of a `ul` or `ol`, with the lists nested in them one level deeper.
+fn html_list(list: &NodeRef, ordered: bool, depth: usize, blocks: &mut Vec<Block>) {
+    for item in list.children().filter(|c| c.as_element().is_some_and(|e| &*e.name.local == "li")) {
+        let mut text = String::new();
+        let mut nested = Vec::new();
+        for child in item.children() {
+            match child.as_element().map(|e| e.name.local.to_string()).as_deref() {
+                Some(name @ ("ul" | "ol")) => nested.push((child.clone(), name == "ol")),
+                _ => text.push_str(&inline_text(&child)),
+            }
+        }
+        blocks.push(Block::new(BlockKind::ListItem { ordered, depth, text: collapse_whitespace(&text) }));
+        for (list, ordered) in nested {
+            html_list(&list, ordered, depth + 1, blocks);
+        }
+    }
+}
+
+/// Text of `node` and everything in it, with `<br>` as a line break and links as
+/// Markdown links.
+fn inline_text(node: &NodeRef) -> String {
+    if let Some(text) = node.as_text() {
+        return source_text(&text.borrow());
+    }
+    let Some(element) = node.as_element() else { return String::new() };
+    let name = &*element.name.local;
+    if HTML_HIDDEN.contains(&name) {
+        return String::new();
+    }
+    if name == "br" {
+        return "\n".to_string();
+    }
+    let text: String = node.children().map(|child| inline_text(&child)).collect();
+    if name != "a" {
+        return text;
+    }
+    let attributes = element.attributes.borrow();
+    let href = attributes.get("href").unwrap_or("").trim();
+    // Anchors within the page and scripts lead nowhere the agent can follow
+    if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") || text.trim().is_empty() {
+        return text;
+    }
+    document::markdown_link(&text, href)
+}
+
+/// Line breaks in the source are only whitespace; `<br>` makes the real ones.
+fn source_text(text: &str) -> String {
+    text.replace(['\n', '\r'], " ")
+}
+
+/// Runs of whitespace as one space, as a browser shows them; explicit line breaks stay.
+fn collapse_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    /// A PDF with one page per entry of `pages`, each showing that text.
+    fn write_pdf(path: &Path, pages: &[&str]) {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! { "Font" => dictionary! { "F1" => font_id } });
+        let kids: Vec<Object> = pages.iter().map(|text| {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![100.into(), 600.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content_id }).into()
+        }).collect();
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        }));
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    fn kinds(document: &Document) -> Vec<BlockKind> {
+        document.blocks.iter().map(|block| block.kind.clone()).collect()
+    }
+
+    fn heading(level: u8, text: &str) -> BlockKind {
+        BlockKind::Heading { level, text: text.to_string() }
+    }
+
+    fn paragraph(text: &str) -> BlockKind {
+        BlockKind::Paragraph { text: text.to_string() }
+    }
+
+    fn item(ordered: bool, depth: usize, text: &str) -> BlockKind {
+        BlockKind::ListItem { ordered, depth, text: text.to_string() }
+    }
+
+    fn table(rows: &[&[&str]]) -> BlockKind {
+        BlockKind::Table { rows: rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect() }
+    }
+
+    #[test]
+    fn pdf_pages_are_numbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        write_pdf(&path, &["Introduction", "Results", "Conclusion"]);
+
+        let document = read_document(path.to_str().unwrap()).unwrap();
+        assert_eq!(document.page_count(), 3);
+        let text = document.page_range(2, 2).unwrap();
+        assert!(text.starts_with("[page 2]\n\n") && text.contains("Results") && !text.contains("Conclusion"), "{}", text);
+        let text = read_file(path.to_str().unwrap()).unwrap();
+        assert!(text.starts_with("[page 1]\n\nIntroduction"), "{}", text);
+    }
+
+    #[test]
+    fn markdown_blocks_keep_their_source_range() {
+        let markdown = "\
+Title
+=====
+
+Some *text*
+over two lines.
+
+- one
+  continued
+  - nested
+1. first
+
+| A | B |
+|---|:-:|
+| 1 | a \\| b |
+
+```rust
+fn main() {}
+```
+
+> quoted
+> twice
+## Closing ##
+";
+        let document = parse_markdown(markdown);
+        assert_eq!(kinds(&document), vec![
+            heading(1, "Title"),
+            paragraph("Some *text*\nover two lines."),
+            item(false, 0, "one\ncontinued"),
+            item(false, 1, "nested"),
+            item(true, 0, "first"),
+            table(&[&["A", "B"], &["1", "a | b"]]),
+            BlockKind::CodeBlock { language: Some("rust".to_string()), code: "fn main() {}".to_string() },
+            BlockKind::Quote { text: "quoted\ntwice".to_string() },
+            heading(2, "Closing"),
+        ]);
+        let sources: Vec<&str> = document.blocks.iter().map(|block| &markdown[block.source.clone().unwrap()]).collect();
+        assert_eq!(sources[0], "Title\n=====");
+        assert_eq!(sources[1], "Some *text*\nover two lines.");
+        assert_eq!(sources[6], "```rust\nfn main() {}\n```");
+        assert_eq!(sources[8], "## Closing ##");
+    }
+
+    #[test]
+    fn plain_text_paragraphs() {
+        let document = parse_plain_text("first\nline\n\n\nsecond  \n");
+        assert_eq!(kinds(&document), vec![paragraph("first\nline"), paragraph("second")]);
+        assert_eq!(document.blocks[1].source, Some(13..19));
+    }
+
+    #[test]
+    fn html_structure_becomes_blocks() {
+        let document = parse_html(r#"<html><body>
+            <h1>Report</h1>
+            <div><p>Some   <b>bold</b>
+               text<br>and a break</p></div>
+            <ul><li>one<ol><li>nested</li></ol></li><li>two</li></ul>
+            <table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td>2</td></tr></table>
+            <pre><code class="language-sh">ls -l
+cd /</code></pre>
+            <blockquote><p>quoted</p></blockquote>
+            loose text
+        </body></html>"#);
+        assert_eq!(kinds(&document), vec![
+            heading(1, "Report"),
+            paragraph("Some bold text\nand a break"),
+            item(false, 0, "one"),
+            item(true, 1, "nested"),
+            item(false, 0, "two"),
+            table(&[&["A", "B"], &["1", "2"]]),
+            BlockKind::CodeBlock { language: Some("sh".to_string()), code: "ls -l\ncd /".to_string() },
+            BlockKind::Quote { text: "quoted".to_string() },
+            paragraph("loose text"),
+        ]);
+    }
+
+    #[test]
+    fn html_drops_scripts_and_keeps_links_and_metadata() {
+        let document = parse_html(r##"<!DOCTYPE html><html><head>
+            <title> Release
+              notes </title>
+            <meta name="Description" content="What changed in 2.0">
+            <meta name="author" content="The team">
+            <style>body { color: red }</style>
+        </head><body>
+            <script>console.log("hidden")</script>
+            <noscript>Enable JavaScript</noscript>
+            <p style="margin: 0">Fish &amp; chips &lt;3&nbsp;&#x2014; see <a href="https://example.com/a b">the docs</a>,
+               <a href="#top">top</a> or <a href="javascript:void(0)">nothing</a>.</p>
+            <ul><li><a href="/faq">FAQ</a><script>var x;</script></li></ul>
+        </body></html>"##);
+        assert_eq!(kinds(&document), vec![
+            paragraph("Fish & chips <3 \u{2014} see [the docs](<https://example.com/a b>), top or nothing."),
+            item(false, 0, "[FAQ](/faq)"),
+        ]);
+        assert!(document.to_markdown().starts_with("# Release notes\n\nBy The team\n\n> What changed in 2.0\n\nFish"));
+    }
+
+    #[test]
+    fn htm_and_xhtml_files_are_html() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["page.htm", "page.xhtml"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "<html><body><h2>Hello</h2><style>h2 {}</style></body></html>").unwrap();
+            assert_eq!(read_file(path.to_str().unwrap()).unwrap(), "## Hello");
+        }
+    }
+
+    #[test]
+    fn text_files_have_no_page_markers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        fs::write(&path, "# Notes").unwrap();
+        let document = read_document(path.to_str().unwrap()).unwrap();
+        assert_eq!(document.page_count(), 0);
+        assert_eq!(read_file(path.to_str().unwrap()).unwrap(), "# Notes");
+        assert!(document.page_range(1, 1).is_err());
+    }
+
+    #[test]
+    fn text_files_come_back_as_they_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        let markdown = "---\ntitle: Notes\n---\n\nSteps:  \nfirst\n\n    let x = 1;\n        nested();\n\n3. three\n4. four\n";
+        fs::write(&path, markdown).unwrap();
+        assert_eq!(read_file(path.to_str().unwrap()).unwrap(), markdown);
+    }
 
-    let mut text = String::new();
-    for child in doc.document.children {
-        if let docx_rs::DocumentChild::Paragraph(p) = child {
-            for child in &p.children {
-                if let docx_rs::ParagraphChild::Run(run) = child {
-                    for child in &run.children {
-                        if let docx_rs::RunChild::Text(t) = child {
-                            text.push_str(&t.text);
-                        }
-                    }
+    #[test]
+    fn broken_packages_fail_with_their_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["broken.docx", "broken.epub"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "not a zip").unwrap();
+            match read_file(path.to_str().unwrap()) {
+                Err(AppError::ExtractionFailed { path: failed, message }) => {
+                    assert_eq!(failed, path.display().to_string());
+                    assert!(message.starts_with("not a "), "{}", message);
                 }
+                other => panic!("{:?}", other),
             }
-            text.push('\n');
         }
     }
-    Ok(text)
 }
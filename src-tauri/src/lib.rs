@@ -1,30 +1,61 @@
-mod acp;
+pub mod acp;
+mod agents;
+mod client_fs;
 mod commands;
+mod document;
+mod docx;
+mod epub;
+pub mod error;
+mod events;
 mod file_reader;
 mod mcp_server;
+mod pool;
+mod prompt;
+mod settings;
+mod supervisor;
+pub mod trace;
+pub mod protocol;
+mod workspace;
+mod xml;
 
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-pub fn run_mcp() {
-    mcp_server::run_mcp_server();
+pub fn run_mcp(workspace: &str) {
+    mcp_server::run_mcp_server(workspace);
 }
 
-type SharedStdin = Arc<std::sync::Mutex<std::process::ChildStdin>>;
-
+/// Every field is shared, so clones (e.g. the supervisor's) see the same state.
+#[derive(Clone)]
 pub struct AppState {
-    pub sessions: Arc<Mutex<HashMap<String, Arc<Mutex<acp::AcpClient>>>>>,
-    pub cancel_handles: Arc<Mutex<HashMap<String, (SharedStdin, String)>>>,
+    pub sessions: Arc<Mutex<HashMap<String, Arc<acp::AcpClient>>>>,
     pub workspace: Arc<Mutex<Option<String>>>,
+    /// Per tool kind answers to agent permission requests; missing kinds ask the user
+    pub permission_policies: Arc<Mutex<HashMap<String, acp::PermissionPolicy>>>,
+    /// Prompt outcomes per session not yet written by `save_session_history`
+    pub unsaved_turns: Arc<Mutex<HashMap<String, Vec<serde_json::Value>>>>,
+    /// The prompt running per session, kept so it can be retried after a crash
+    pub interrupted_prompts: Arc<Mutex<HashMap<String, Vec<prompt::PromptPart>>>>,
+    /// Loaded from the settings file at startup, replaced by `save_settings`
+    pub settings: Arc<Mutex<settings::Settings>>,
+    /// Initialized agent processes waiting for the next new or reloaded session
+    pub pool: Arc<pool::ProcessPool>,
+    /// Workspace, agent and last use of every open session, live or suspended
+    pub session_meta: Arc<Mutex<HashMap<String, pool::SessionMeta>>>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let state = AppState {
         sessions: Arc::new(Mutex::new(HashMap::new())),
-        cancel_handles: Arc::new(Mutex::new(HashMap::new())),
         workspace: Arc::new(Mutex::new(None)),
+        permission_policies: Arc::new(Mutex::new(HashMap::new())),
+        unsaved_turns: Arc::new(Mutex::new(HashMap::new())),
+        interrupted_prompts: Arc::new(Mutex::new(HashMap::new())),
+        settings: Arc::new(Mutex::new(settings::load())),
+        pool: Arc::new(pool::ProcessPool::default()),
+        session_meta: Arc::new(Mutex::new(HashMap::new())),
     };
 
     tauri::Builder::default()
@@ -39,8 +70,21 @@ pub fn run() {
             commands::new_acp_session,
             commands::load_acp_session,
             commands::send_prompt,
+            commands::retry_prompt,
+            commands::discard_interrupted_prompt,
             commands::cancel_prompt,
+            commands::respond_permission,
+            commands::get_permission_policies,
+            commands::set_permission_policy,
             commands::set_model,
+            commands::set_mode,
+            commands::get_session_capabilities,
+            commands::get_session_diagnostics,
+            commands::restart_session,
+            commands::list_agents,
+            commands::get_settings,
+            commands::save_settings,
+            commands::check_kiro_cli,
             commands::close_acp_session,
             commands::save_session_history,
             commands::load_session_history,
@@ -2,8 +2,10 @@
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.iter().any(|a| a == "--mcp-server") {
-        kiro_notebook_lib::run_mcp();
+    if let Some(pos) = args.iter().position(|a| a == "--mcp-server") {
+        // The workspace root follows the flag; default to the current directory
+        let workspace = args.get(pos + 1).map(String::as_str).unwrap_or(".");
+        kiro_notebook_lib::run_mcp(workspace);
     } else {
         kiro_notebook_lib::run();
     }
@@ -1,9 +1,19 @@
 use serde_json::{json, Value};
 use std::io::{self, BufRead, Write};
+use std::path::Path;
 
+use crate::error::AppError;
 use crate::file_reader;
+use crate::workspace::resolve_in_workspace;
 
-pub fn run_mcp_server() {
+pub fn run_mcp_server(workspace: &str) {
+    let root = match std::fs::canonicalize(workspace) {
+        Ok(root) => root,
+        Err(e) => {
+            eprintln!("[MCP] Invalid workspace {}: {}", workspace, e);
+            return;
+        }
+    };
     let stdin = io::stdin();
     let mut stdout = io::stdout();
 
@@ -34,29 +44,42 @@ pub fn run_mcp_server() {
                 "tools": [
                     {
                         "name": "read_file",
-                        "description": "Read the text content of a file. Supports PDF, DOCX, MD, TXT, HTML files.",
+                        "description": "Read the text content of a file. Supports PDF, DOCX, EPUB, MD, TXT, HTML files; web pages come back as Markdown without scripts or styles. PDF text is split by [page N] lines; cite pages by those numbers.",
                         "inputSchema": {
                             "type": "object",
                             "properties": {
-                                "path": { "type": "string", "description": "Absolute path to the file" }
+                                "path": { "type": "string", "description": "Path to the file, absolute or relative to the workspace root" }
                             },
                             "required": ["path"]
                         }
                     },
+                    {
+                        "name": "read_file_pages",
+                        "description": "Read pages `from` to `to` (1-based, inclusive) of a PDF, each introduced by a [page N] line. Use it for long documents instead of read_file.",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {
+                                "path": { "type": "string", "description": "Path to the file, absolute or relative to the workspace root" },
+                                "from": { "type": "integer", "minimum": 1, "description": "First page" },
+                                "to": { "type": "integer", "minimum": 1, "description": "Last page; defaults to `from`" }
+                            },
+                            "required": ["path", "from"]
+                        }
+                    },
                     {
                         "name": "list_files",
-                        "description": "List files in a directory. Returns file names and paths.",
+                        "description": "List files in a workspace directory. Returns file names and paths.",
                         "inputSchema": {
                             "type": "object",
                             "properties": {
-                                "directory": { "type": "string", "description": "Absolute path to directory" }
+                                "directory": { "type": "string", "description": "Path to the directory, absolute or relative to the workspace root" }
                             },
                             "required": ["directory"]
                         }
                     },
                     {
                         "name": "search_content",
-                        "description": "Search for text across multiple files. Returns matching lines with file paths.",
+                        "description": "Search for text across multiple files. Returns matching lines with file paths, and the page for PDF files.",
                         "inputSchema": {
                             "type": "object",
                             "properties": {
@@ -64,7 +87,7 @@ pub fn run_mcp_server() {
                                 "files": {
                                     "type": "array",
                                     "items": { "type": "string" },
-                                    "description": "List of absolute file paths to search in"
+                                    "description": "List of file paths to search in, absolute or relative to the workspace root"
                                 }
                             },
                             "required": ["query", "files"]
@@ -72,7 +95,7 @@ pub fn run_mcp_server() {
                     }
                 ]
             }),
-            "tools/call" => handle_tool_call(&request),
+            "tools/call" => handle_tool_call(&request, &root),
             "notifications/initialized" => {
                 continue;
             }
@@ -92,7 +115,21 @@ pub fn run_mcp_server() {
     }
 }
 
-fn handle_tool_call(request: &Value) -> Value {
+/// The message for the model, with the error itself under `_meta` for clients that look.
+fn tool_error(error: AppError) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": format!("Error: {}", error) }],
+        "isError": true,
+        "_meta": { "error": error }
+    })
+}
+
+/// Page numbers beyond `u32` are out of range anyway
+fn clamp(page: u64) -> u32 {
+    u32::try_from(page).unwrap_or(u32::MAX)
+}
+
+fn handle_tool_call(request: &Value, root: &Path) -> Value {
     let params = request.get("params").unwrap_or(&Value::Null);
     let tool_name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
     let arguments = params.get("arguments").unwrap_or(&Value::Null);
@@ -100,29 +137,56 @@ fn handle_tool_call(request: &Value) -> Value {
     match tool_name {
         "read_file" => {
             let path = arguments.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            match file_reader::read_file(path) {
+            let path = match resolve_in_workspace(root, path) {
+                Ok(p) => p,
+                Err(e) => return tool_error(e),
+            };
+            match file_reader::read_file(&path.to_string_lossy()) {
                 Ok(content) => json!({
                     "content": [{ "type": "text", "text": content }]
                 }),
-                Err(e) => json!({
-                    "content": [{ "type": "text", "text": format!("Error: {}", e) }],
-                    "isError": true
+                Err(e) => tool_error(e),
+            }
+        }
+        "read_file_pages" => {
+            let path = arguments.get("path").and_then(|v| v.as_str()).unwrap_or("");
+            let Some(from) = arguments.get("from").and_then(|v| v.as_u64()) else {
+                return tool_error(AppError::InvalidArgument { name: "from".to_string(), message: "must be a page number".to_string() });
+            };
+            let to = arguments.get("to").and_then(|v| v.as_u64()).unwrap_or(from);
+            let path = match resolve_in_workspace(root, path) {
+                Ok(p) => p,
+                Err(e) => return tool_error(e),
+            };
+            let pages = file_reader::read_document(&path.to_string_lossy())
+                .and_then(|document| document.page_range(clamp(from), clamp(to)));
+            match pages {
+                Ok(content) => json!({
+                    "content": [{ "type": "text", "text": content }]
                 }),
+                Err(e) => tool_error(e),
             }
         }
         "list_files" => {
             let dir = arguments.get("directory").and_then(|v| v.as_str()).unwrap_or("");
-            let extensions = ["pdf", "docx", "md", "txt", "html"];
+            let dir = match resolve_in_workspace(root, dir) {
+                Ok(d) => d,
+                Err(e) => return tool_error(e),
+            };
+            let extensions = ["pdf", "docx", "md", "txt", "html", "htm", "xhtml", "epub"];
             let mut files = Vec::new();
 
-            if let Ok(entries) = std::fs::read_dir(dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                            if extensions.contains(&ext.to_lowercase().as_str()) {
-                                files.push(path.display().to_string());
-                            }
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => return tool_error(AppError::io(&dir, e)),
+            };
+            for entry in entries.flatten() {
+                // Symlinked entries may point outside the workspace
+                let Ok(path) = resolve_in_workspace(root, &entry.path().to_string_lossy()) else { continue };
+                if path.is_file() {
+                    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+                        if extensions.contains(&ext.to_lowercase().as_str()) {
+                            files.push(path.display().to_string());
                         }
                     }
                 }
@@ -147,14 +211,32 @@ fn handle_tool_call(request: &Value) -> Value {
                 })
                 .unwrap_or_default();
 
+            let files = match files.iter().map(|f| resolve_in_workspace(root, f)).collect::<Result<Vec<_>, _>>() {
+                Ok(files) => files,
+                Err(e) => return tool_error(e),
+            };
+
             let query_lower = query.to_lowercase();
             let mut results = Vec::new();
 
             for file_path in files {
-                if let Ok(content) = file_reader::read_file(file_path) {
-                    for (i, line) in content.lines().enumerate() {
+                // Line numbers of text files are those of the file itself
+                let pages = if file_reader::needs_extraction(&file_path) {
+                    let Ok(document) = file_reader::read_document(&file_path.to_string_lossy()) else { continue };
+                    document.pages()
+                } else {
+                    let Ok(text) = file_reader::read_file(&file_path.to_string_lossy()) else { continue };
+                    vec![(None, text)]
+                };
+                for (number, text) in pages {
+                    // Lines count from the top of the page for paged files
+                    let location = match number {
+                        Some(number) => format!("{} [page {}]", file_path.display(), number),
+                        None => file_path.display().to_string(),
+                    };
+                    for (i, line) in text.lines().enumerate() {
                         if line.to_lowercase().contains(&query_lower) {
-                            results.push(format!("{}:{}: {}", file_path, i + 1, line));
+                            results.push(format!("{}:{}: {}", location, i + 1, line));
                         }
                     }
                 }
@@ -164,9 +246,169 @@ fn handle_tool_call(request: &Value) -> Value {
                 "content": [{ "type": "text", "text": results.join("\n") }]
             })
         }
-        _ => json!({
-            "content": [{ "type": "text", "text": format!("Unknown tool: {}", tool_name) }],
-            "isError": true
-        }),
+        _ => tool_error(AppError::UnknownTool { name: tool_name.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        root: PathBuf,
+        outside: PathBuf,
+    }
+
+    /// A workspace with `notes.md` and `sub/inner.txt`, next to a sibling directory
+    /// holding `secret.txt` that must stay unreachable.
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(dir.path()).unwrap();
+        let root = base.join("workspace");
+        let outside = base.join("outside");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("notes.md"), "hello workspace").unwrap();
+        fs::write(root.join("sub/inner.txt"), "inner text").unwrap();
+        fs::write(outside.join("secret.txt"), "top secret").unwrap();
+        Fixture { _dir: dir, root, outside }
+    }
+
+    fn call(root: &Path, name: &str, arguments: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments }
+        });
+        handle_tool_call(&request, root)
+    }
+
+    fn is_error(result: &Value) -> bool {
+        result.get("isError").and_then(|v| v.as_bool()).unwrap_or(false)
+    }
+
+    fn text(result: &Value) -> &str {
+        result.pointer("/content/0/text").and_then(|v| v.as_str()).unwrap_or("")
+    }
+
+    #[test]
+    fn read_file_inside_workspace() {
+        let f = fixture();
+        let absolute = call(&f.root, "read_file", json!({ "path": f.root.join("notes.md") }));
+        assert!(!is_error(&absolute));
+        assert_eq!(text(&absolute), "hello workspace");
+
+        let relative = call(&f.root, "read_file", json!({ "path": "sub/inner.txt" }));
+        assert!(!is_error(&relative));
+        assert_eq!(text(&relative), "inner text");
+    }
+
+    #[test]
+    fn read_file_rejects_absolute_path_outside() {
+        let f = fixture();
+        let result = call(&f.root, "read_file", json!({ "path": f.outside.join("secret.txt") }));
+        assert!(is_error(&result));
+        assert!(text(&result).contains("outside the workspace"));
+        assert_eq!(result.pointer("/_meta/error/kind"), Some(&json!("accessDenied")));
+    }
+
+    #[test]
+    fn read_file_rejects_dot_dot_traversal() {
+        let f = fixture();
+        let result = call(&f.root, "read_file", json!({ "path": "../outside/secret.txt" }));
+        assert!(is_error(&result));
+        assert!(!text(&result).contains("top secret"));
+
+        let sneaky = f.root.join("sub/../../outside/secret.txt");
+        let result = call(&f.root, "read_file", json!({ "path": sneaky }));
+        assert!(is_error(&result));
+    }
+
+    #[test]
+    fn read_file_allows_dot_dot_that_stays_inside() {
+        let f = fixture();
+        let result = call(&f.root, "read_file", json!({ "path": "sub/../notes.md" }));
+        assert!(!is_error(&result));
+        assert_eq!(text(&result), "hello workspace");
+    }
+
+    #[test]
+    fn read_file_rejects_missing_path() {
+        let f = fixture();
+        assert!(is_error(&call(&f.root, "read_file", json!({}))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_file_rejects_symlink_escape() {
+        let f = fixture();
+        std::os::unix::fs::symlink(f.outside.join("secret.txt"), f.root.join("link.txt")).unwrap();
+        let result = call(&f.root, "read_file", json!({ "path": "link.txt" }));
+        assert!(is_error(&result));
+        assert!(!text(&result).contains("top secret"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_files_rejects_symlinked_directory_escape() {
+        let f = fixture();
+        std::os::unix::fs::symlink(&f.outside, f.root.join("escape")).unwrap();
+        let result = call(&f.root, "list_files", json!({ "directory": "escape" }));
+        assert!(is_error(&result));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_files_skips_symlinked_files_pointing_outside() {
+        let f = fixture();
+        std::os::unix::fs::symlink(f.outside.join("secret.txt"), f.root.join("link.txt")).unwrap();
+        let result = call(&f.root, "list_files", json!({ "directory": f.root }));
+        assert!(!is_error(&result));
+        assert!(text(&result).contains("notes.md"));
+        assert!(!text(&result).contains("link.txt"));
+        assert!(!text(&result).contains("secret.txt"));
+    }
+
+    #[test]
+    fn list_files_rejects_parent_directory() {
+        let f = fixture();
+        let result = call(&f.root, "list_files", json!({ "directory": ".." }));
+        assert!(is_error(&result));
+    }
+
+    #[test]
+    fn search_content_rejects_any_file_outside() {
+        let f = fixture();
+        let result = call(&f.root, "search_content", json!({
+            "query": "secret",
+            "files": ["notes.md", f.outside.join("secret.txt")]
+        }));
+        assert!(is_error(&result));
+        assert!(!text(&result).contains("top secret"));
+    }
+
+    #[test]
+    fn read_file_pages_needs_a_paged_file() {
+        let f = fixture();
+        let result = call(&f.root, "read_file_pages", json!({ "path": "notes.md", "from": 1 }));
+        assert!(is_error(&result));
+        assert!(text(&result).contains("no pages"), "{}", text(&result));
+        assert!(is_error(&call(&f.root, "read_file_pages", json!({ "path": "notes.md" }))));
+    }
+
+    #[test]
+    fn search_content_inside_workspace() {
+        let f = fixture();
+        let result = call(&f.root, "search_content", json!({
+            "query": "INNER",
+            "files": ["sub/inner.txt", "notes.md"]
+        }));
+        assert!(!is_error(&result));
+        assert!(text(&result).contains("inner.txt:1: inner text"));
+        assert!(!text(&result).contains("notes.md"));
     }
 }
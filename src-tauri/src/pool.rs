@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::acp::{AcpClient, SessionCapabilities, Timeouts};
+use crate::agents::AgentProfile;
+use crate::commands;
+
+fn log(msg: &str) {
+    eprintln!("[POOL] {}", msg);
+}
+
+/// Where an open session runs, kept for reloading it after it was suspended.
+#[derive(Clone, Debug)]
+pub struct SessionMeta {
+    pub cwd: String,
+    pub profile: AgentProfile,
+    pub last_used: Instant,
+    /// Set while the process is stopped: what the session offered when it was suspended
+    pub suspended: Option<SessionCapabilities>,
+}
+
+struct Warm {
+    cwd: String,
+    profile: AgentProfile,
+    client: AcpClient,
+}
+
+/// The refill running in the background and what it starts processes for
+struct Refill {
+    generation: u64,
+    cwd: String,
+    profile: AgentProfile,
+}
+
+/// Agent processes that have run `initialize` and wait for a new or reloaded session,
+/// so a tab does not pay for the spawn and handshake.
+#[derive(Default)]
+pub struct ProcessPool {
+    /// Oldest first
+    idle: Mutex<Vec<Warm>>,
+    /// Bumped by `clear` and by a refill for another workspace or agent; a refill
+    /// started under an older one throws away what it starts
+    generation: AtomicU64,
+    refilling: StdMutex<Option<Refill>>,
+}
+
+impl ProcessPool {
+    /// A warm process for `profile` in `cwd`, if one is ready and still alive.
+    pub async fn claim(&self, cwd: &str, profile: &AgentProfile) -> Option<AcpClient> {
+        let mut idle = self.idle.lock().await;
+        // Dead ones are of no use to anybody
+        idle.retain(|warm| !warm.client.is_closed());
+        let index = idle.iter().position(|warm| warm.cwd == cwd && &warm.profile == profile)?;
+        log(&format!("Claimed a warm {} process", profile.id));
+        Some(idle.remove(index).client)
+    }
+
+    pub async fn len(&self) -> usize {
+        self.idle.lock().await.len()
+    }
+
+    /// Stop the oldest warm process; `false` when there was none.
+    pub async fn evict_one(&self) -> bool {
+        let mut idle = self.idle.lock().await;
+        if idle.is_empty() {
+            return false;
+        }
+        idle.remove(0);
+        true
+    }
+
+    /// Stop every warm process, e.g. when the workspace or the settings change. A
+    /// refill still running stops too, and drops the process it is starting.
+    pub async fn clear(&self) {
+        let mut idle = self.idle.lock().await;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        idle.clear();
+    }
+
+    /// Start up to `count` processes for `profile` in `cwd` in the background. Does
+    /// nothing while a refill for the same is running; one for anything else is replaced.
+    pub fn refill(self: &Arc<Self>, cwd: String, profile: AgentProfile, timeouts: Timeouts, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let Some(generation) = self.start_refill(&cwd, &profile) else { return };
+        let pool = self.clone();
+        tokio::spawn(async move {
+            for _ in 0..count {
+                let client = match commands::spawn_and_init(&cwd, &profile, timeouts).await {
+                    Ok(client) => client,
+                    Err(e) => {
+                        log(&format!("Could not start a warm {} process: {}", profile.id, e));
+                        break;
+                    }
+                };
+                let mut idle = pool.idle.lock().await;
+                // Cleared or replaced meanwhile: `client` is dropped, which stops it
+                if !pool.is_current(generation) {
+                    break;
+                }
+                idle.push(Warm { cwd: cwd.clone(), profile: profile.clone(), client });
+            }
+            pool.finish_refill(generation);
+        });
+    }
+
+    /// The generation a refill for `profile` in `cwd` runs under, or `None` when one
+    /// for the same is already running.
+    fn start_refill(&self, cwd: &str, profile: &AgentProfile) -> Option<u64> {
+        let mut refilling = self.refilling.lock().ok()?;
+        let current = self.generation.load(Ordering::SeqCst);
+        let generation = match refilling.as_ref() {
+            Some(refill) if refill.generation == current && refill.cwd == cwd && &refill.profile == profile => return None,
+            // Its processes would be for another workspace or agent
+            Some(refill) if refill.generation == current => self.generation.fetch_add(1, Ordering::SeqCst) + 1,
+            _ => current,
+        };
+        *refilling = Some(Refill { generation, cwd: cwd.to_string(), profile: profile.clone() });
+        Some(generation)
+    }
+
+    fn finish_refill(&self, generation: u64) {
+        if let Ok(mut refilling) = self.refilling.lock() {
+            if refilling.as_ref().is_some_and(|refill| refill.generation == generation) {
+                *refilling = None;
+            }
+        }
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Warm processes ready for `profile` in `cwd`.
+    pub async fn ready(&self, cwd: &str, profile: &AgentProfile) -> usize {
+        self.idle.lock().await.iter()
+            .filter(|warm| warm.cwd == cwd && &warm.profile == profile)
+            .count()
+    }
+}
+
+/// The live session used longest ago among those not running a prompt.
+pub fn least_recently_used<'a>(
+    candidates: impl IntoIterator<Item = (&'a str, bool)>,
+    meta: &HashMap<String, SessionMeta>,
+) -> Option<String> {
+    candidates
+        .into_iter()
+        .filter(|(_, busy)| !busy)
+        .min_by_key(|(id, _)| meta.get(*id).map(|m| m.last_used))
+        .map(|(id, _)| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn meta(last_used: Instant) -> SessionMeta {
+        SessionMeta {
+            cwd: "/ws".to_string(),
+            profile: AgentProfile::default(),
+            last_used,
+            suspended: None,
+        }
+    }
+
+    #[test]
+    fn least_recently_used_skips_busy_sessions() {
+        let now = Instant::now();
+        let meta = HashMap::from([
+            ("first".to_string(), meta(now)),
+            ("second".to_string(), meta(now + Duration::from_secs(1))),
+            ("third".to_string(), meta(now + Duration::from_secs(2))),
+        ]);
+        let candidates = [("third", false), ("first", true), ("second", false)];
+        assert_eq!(least_recently_used(candidates, &meta).as_deref(), Some("second"));
+        assert_eq!(least_recently_used([("first", true)], &meta), None);
+    }
+
+    fn profile(id: &str) -> AgentProfile {
+        AgentProfile { id: id.to_string(), ..AgentProfile::default() }
+    }
+
+    #[tokio::test]
+    async fn refills_for_another_target_or_after_clear_replace_the_running_one() {
+        let pool = ProcessPool::default();
+        let first = pool.start_refill("/ws", &profile("kiro")).unwrap();
+        assert_eq!(pool.start_refill("/ws", &profile("kiro")), None);
+
+        let other = pool.start_refill("/other", &profile("kiro")).unwrap();
+        assert!(!pool.is_current(first) && pool.is_current(other));
+        // The replaced refill finishing leaves the new one running
+        pool.finish_refill(first);
+        assert_eq!(pool.start_refill("/other", &profile("kiro")), None);
+
+        pool.clear().await;
+        assert!(!pool.is_current(other));
+        let after_clear = pool.start_refill("/other", &profile("kiro")).unwrap();
+        assert!(pool.is_current(after_clear));
+        pool.finish_refill(after_clear);
+        assert!(pool.start_refill("/other", &profile("kiro")).is_some());
+    }
+
+    #[test]
+    fn sessions_without_meta_go_first() {
+        let meta = HashMap::from([("known".to_string(), meta(Instant::now()))]);
+        let candidates = [("known", false), ("unknown", false)];
+        assert_eq!(least_recently_used(candidates, &meta).as_deref(), Some("unknown"));
+    }
+}
@@ -0,0 +1,301 @@
+use base64::Engine;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::AppError;
+use crate::file_reader;
+use crate::protocol::{
+    BlobResourceContents, ContentBlock, EmbeddedResource, ImageContent, PromptCapabilities,
+    ResourceContents, ResourceLink, TextResourceContents,
+};
+use crate::workspace::resolve_in_workspace;
+
+/// Characters of extracted text embedded per document.
+pub const MAX_RESOURCE_CHARS: usize = 50_000;
+
+/// One piece of a prompt as the UI sends it. Files are referenced by path and
+/// resolved against the workspace here.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromptPart {
+    Text { text: String },
+    /// PNG or JPEG, sent inline as base64
+    Image { path: String },
+    /// A document whose extracted text is embedded in the prompt
+    Resource { path: String },
+    /// A file the agent reads itself, through `fs/read_text_file` or its MCP tools
+    ResourceLink { path: String },
+}
+
+/// Turns the UI's prompt parts into ACP content blocks.
+///
+/// Embedded resources fall back to links when the agent does not advertise
+/// `embeddedContext`; images are refused when it does not advertise `image`.
+pub fn build_blocks(
+    parts: &[PromptPart],
+    workspace: &Path,
+    capabilities: &PromptCapabilities,
+) -> Result<Vec<ContentBlock>, AppError> {
+    let workspace = fs::canonicalize(workspace).map_err(|e| AppError::from_io(workspace, e))?;
+    parts.iter().map(|part| match part {
+        PromptPart::Text { text } => Ok(ContentBlock::text(text.as_str())),
+        PromptPart::Image { path } => {
+            if !capabilities.image {
+                return Err(AppError::UnsupportedFileType { extension: extension(Path::new(path)) });
+            }
+            image_block(&attached_path(&workspace, path)?)
+        }
+        PromptPart::Resource { path } if capabilities.embedded_context => {
+            Ok(resource_block(&attached_path(&workspace, path)?))
+        }
+        PromptPart::Resource { path } | PromptPart::ResourceLink { path } => {
+            Ok(resource_link(&attached_path(&workspace, path)?))
+        }
+    }).collect()
+}
+
+/// `path` resolved in the workspace. A file that was moved or deleted since it was
+/// attached does not stop the prompt; its block reports the error instead. Only paths
+/// leading out of the workspace are refused.
+fn attached_path(workspace: &Path, path: &str) -> Result<PathBuf, AppError> {
+    match resolve_in_workspace(workspace, path) {
+        Err(AppError::FileNotFound { .. } | AppError::Io { .. }) => {
+            let joined = workspace.join(path);
+            let escapes = Path::new(path).components().any(|c| matches!(c, Component::ParentDir));
+            if escapes || !joined.starts_with(workspace) {
+                return Err(AppError::AccessDenied { path: path.to_string() });
+            }
+            Ok(joined)
+        }
+        resolved => resolved,
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+fn image_mime_type(path: &Path) -> Option<&'static str> {
+    match extension(path).as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        _ => None,
+    }
+}
+
+/// MIME type of the original file, as advertised on links
+fn mime_type(path: &Path) -> Option<&'static str> {
+    match extension(path).as_str() {
+        "pdf" => Some("application/pdf"),
+        "docx" => Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+        "md" => Some("text/markdown"),
+        "txt" => Some("text/plain"),
+        "html" | "htm" => Some("text/html"),
+        "xhtml" => Some("application/xhtml+xml"),
+        "epub" => Some("application/epub+zip"),
+        _ => image_mime_type(path),
+    }
+}
+
+/// MIME type of the text `file_reader` returns for the file: plain text as it is,
+/// everything else rendered as Markdown
+fn text_mime_type(path: &Path) -> &'static str {
+    match extension(path).as_str() {
+        "txt" => "text/plain",
+        _ => "text/markdown",
+    }
+}
+
+/// `file://` URI for an absolute path, percent-encoding everything but unreserved characters
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+fn image_block(path: &Path) -> Result<ContentBlock, AppError> {
+    let mime_type = image_mime_type(path)
+        .ok_or_else(|| AppError::UnsupportedFileType { extension: extension(path) })?;
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return Ok(ContentBlock::text(unreadable(path, &e))),
+    };
+    Ok(ContentBlock::Image(ImageContent {
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        mime_type: mime_type.to_string(),
+        uri: Some(file_uri(path)),
+    }))
+}
+
+/// Documents go through `file_reader` so the agent gets text; long ones are truncated.
+/// Images are embedded as blobs. Files that cannot be read are embedded with the
+/// error, so the agent can say so.
+fn resource_block(path: &Path) -> ContentBlock {
+    let uri = file_uri(path);
+    let contents = match image_mime_type(path) {
+        Some(mime_type) => fs::read(path)
+            .map(|bytes| ResourceContents::Blob(BlobResourceContents {
+                uri: uri.clone(),
+                mime_type: Some(mime_type.to_string()),
+                blob: base64::engine::general_purpose::STANDARD.encode(bytes),
+            }))
+            .map_err(|e| AppError::ExtractionFailed { path: path.display().to_string(), message: e.to_string() }),
+        None => file_reader::read_file(&path.to_string_lossy())
+            .map(|content| ResourceContents::Text(TextResourceContents {
+                uri: uri.clone(),
+                mime_type: Some(text_mime_type(path).to_string()),
+                text: truncate(content, MAX_RESOURCE_CHARS),
+            })),
+    };
+    let resource = contents.unwrap_or_else(|e| ResourceContents::Text(TextResourceContents {
+        uri,
+        mime_type: Some("text/plain".to_string()),
+        text: unreadable(path, &e),
+    }));
+    ContentBlock::Resource(EmbeddedResource { resource })
+}
+
+fn unreadable(path: &Path, error: &dyn std::fmt::Display) -> String {
+    format!("[Error reading {}: {}]", file_name(path), error)
+}
+
+fn resource_link(path: &Path) -> ContentBlock {
+    ContentBlock::ResourceLink(ResourceLink {
+        uri: file_uri(path),
+        name: file_name(path),
+        mime_type: mime_type(path).map(str::to_string),
+        title: None,
+        description: None,
+        size: fs::metadata(path).ok().map(|m| m.len()),
+    })
+}
+
+fn truncate(content: String, max_chars: usize) -> String {
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...\n[truncated]", &content[..end]),
+        None => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_capabilities() -> PromptCapabilities {
+        PromptCapabilities { image: true, audio: false, embedded_context: true }
+    }
+
+    fn workspace() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.md"), "# Notes").unwrap();
+        fs::write(dir.path().join("chart.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        dir
+    }
+
+    #[test]
+    fn text_and_embedded_resource() {
+        let dir = workspace();
+        let blocks = build_blocks(&[
+            PromptPart::Resource { path: "notes.md".to_string() },
+            PromptPart::Text { text: "Summarise".to_string() },
+        ], dir.path(), &all_capabilities()).unwrap();
+
+        let ContentBlock::Resource(EmbeddedResource { resource: ResourceContents::Text(resource) }) = &blocks[0] else {
+            panic!("expected a text resource: {:?}", blocks[0]);
+        };
+        assert!(resource.uri.starts_with("file:///") && resource.uri.ends_with("/notes.md"));
+        assert_eq!(resource.mime_type.as_deref(), Some("text/markdown"));
+        assert_eq!(resource.text, "# Notes");
+        assert_eq!(blocks[1], ContentBlock::text("Summarise"));
+    }
+
+    #[test]
+    fn resource_falls_back_to_link_without_embedded_context() {
+        let dir = workspace();
+        let capabilities = PromptCapabilities { embedded_context: false, ..all_capabilities() };
+        let blocks = build_blocks(&[PromptPart::Resource { path: "notes.md".to_string() }], dir.path(), &capabilities).unwrap();
+        let ContentBlock::ResourceLink(link) = &blocks[0] else { panic!("expected a link: {:?}", blocks[0]) };
+        assert_eq!(link.name, "notes.md");
+        assert_eq!(link.mime_type.as_deref(), Some("text/markdown"));
+        assert_eq!(link.size, Some(7));
+    }
+
+    #[test]
+    fn image_is_base64_encoded() {
+        let dir = workspace();
+        let blocks = build_blocks(&[PromptPart::Image { path: "chart.png".to_string() }], dir.path(), &all_capabilities()).unwrap();
+        let ContentBlock::Image(image) = &blocks[0] else { panic!("expected an image: {:?}", blocks[0]) };
+        assert_eq!(image.mime_type, "image/png");
+        assert_eq!(image.data, "iVBORw==");
+    }
+
+    #[test]
+    fn image_requires_capability_and_known_type() {
+        let dir = workspace();
+        let capabilities = PromptCapabilities { image: false, ..all_capabilities() };
+        assert!(build_blocks(&[PromptPart::Image { path: "chart.png".to_string() }], dir.path(), &capabilities).is_err());
+        let err = build_blocks(&[PromptPart::Image { path: "notes.md".to_string() }], dir.path(), &all_capabilities()).unwrap_err();
+        assert_eq!(err, AppError::UnsupportedFileType { extension: "md".to_string() });
+    }
+
+    #[test]
+    fn paths_outside_the_workspace_are_rejected() {
+        let dir = workspace();
+        let outside = tempfile::NamedTempFile::new().unwrap();
+        let path = outside.path().to_string_lossy().to_string();
+        let err = build_blocks(&[PromptPart::ResourceLink { path: path.clone() }], dir.path(), &all_capabilities()).unwrap_err();
+        assert_eq!(err, AppError::AccessDenied { path });
+    }
+
+    #[test]
+    fn missing_files_are_reported_in_the_prompt() {
+        let dir = workspace();
+        let blocks = build_blocks(&[
+            PromptPart::Resource { path: "gone.md".to_string() },
+            PromptPart::Image { path: "gone.png".to_string() },
+            PromptPart::ResourceLink { path: "gone.txt".to_string() },
+            PromptPart::Text { text: "Summarise".to_string() },
+        ], dir.path(), &all_capabilities()).unwrap();
+
+        let ContentBlock::Resource(EmbeddedResource { resource: ResourceContents::Text(resource) }) = &blocks[0] else {
+            panic!("expected a text resource: {:?}", blocks[0]);
+        };
+        assert!(resource.text.starts_with("[Error reading gone.md: "), "{}", resource.text);
+        let ContentBlock::Text(image) = &blocks[1] else { panic!("expected text: {:?}", blocks[1]) };
+        assert!(image.text.starts_with("[Error reading gone.png: "), "{}", image.text);
+        let ContentBlock::ResourceLink(link) = &blocks[2] else { panic!("expected a link: {:?}", blocks[2]) };
+        assert_eq!(link.size, None);
+
+        let err = build_blocks(&[PromptPart::Resource { path: "../gone.md".to_string() }], dir.path(), &all_capabilities()).unwrap_err();
+        assert_eq!(err, AppError::AccessDenied { path: "../gone.md".to_string() });
+    }
+
+    #[test]
+    fn extracted_documents_are_markdown() {
+        for name in ["a.pdf", "a.docx", "a.epub", "a.html", "a.md"] {
+            assert_eq!(text_mime_type(Path::new(name)), "text/markdown", "{}", name);
+        }
+        assert_eq!(text_mime_type(Path::new("a.txt")), "text/plain");
+    }
+
+    #[test]
+    fn file_uri_encodes_spaces() {
+        assert_eq!(file_uri(Path::new("/ws/My Notes/a.md")), "file:///ws/My%20Notes/a.md");
+    }
+
+    #[test]
+    fn truncate_respects_char_boundaries() {
+        assert_eq!(truncate("héllo".to_string(), 2), "hé...\n[truncated]");
+        assert_eq!(truncate("hi".to_string(), 2), "hi");
+    }
+}
@@ -0,0 +1,1041 @@
+//! Typed model of the ACP messages this client sends and understands.
+//! Field names follow the wire format (camelCase), see `.agent/acp-reference.md`.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+pub const PROTOCOL_VERSION: u32 = 1;
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+// JSON-RPC 2.0 envelope shared by both directions
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    String(String),
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestId::Number(n) => write!(f, "{}", n),
+            RequestId::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Request<P> {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub method: String,
+    pub params: P,
+}
+
+impl<P> Request<P> {
+    pub fn new(id: RequestId, method: &str, params: P) -> Self {
+        Self { jsonrpc: JSONRPC_VERSION.to_string(), id, method: method.to_string(), params }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Notification<P> {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: P,
+}
+
+impl<P> Notification<P> {
+    pub fn new(method: &str, params: P) -> Self {
+        Self { jsonrpc: JSONRPC_VERSION.to_string(), method: method.to_string(), params }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Response<R> {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    #[serde(flatten)]
+    pub payload: ResponsePayload<R>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ResponsePayload<R> {
+    Result(R),
+    Error(RpcError),
+}
+
+impl<R> Response<R> {
+    pub fn result(id: RequestId, result: R) -> Self {
+        Self { jsonrpc: JSONRPC_VERSION.to_string(), id, payload: ResponsePayload::Result(result) }
+    }
+
+    pub fn error(id: RequestId, error: RpcError) -> Self {
+        Self { jsonrpc: JSONRPC_VERSION.to_string(), id, payload: ResponsePayload::Error(error) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    /// ACP's code for an unknown session or file
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(Self::METHOD_NOT_FOUND, format!("Method not found: {}", method))
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)?;
+        if let Some(data) = &self.data {
+            write!(f, ": {}", data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Any line read from the agent, before it is known to be a request, response or
+/// notification.
+#[derive(Deserialize, Clone, Debug)]
+pub struct IncomingMessage {
+    pub id: Option<RequestId>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub params: Value,
+    pub result: Option<Value>,
+    pub error: Option<RpcError>,
+}
+
+impl IncomingMessage {
+    /// `params.sessionId`, which every session-scoped message carries
+    pub fn session_id(&self) -> Option<&str> {
+        self.params.get("sessionId").and_then(|v| v.as_str())
+    }
+
+    pub fn parse_params<P: DeserializeOwned>(&self) -> Result<P, RpcError> {
+        serde_json::from_value(self.params.clone())
+            .map_err(|e| RpcError::new(RpcError::INVALID_PARAMS, format!("Invalid params: {}", e)))
+    }
+}
+
+/// A client-to-agent request: ties the params type to its method name and response.
+pub trait AcpRequest: Serialize {
+    const METHOD: &'static str;
+    type Response: DeserializeOwned;
+}
+
+// `initialize` handshake
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeRequest {
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub client_capabilities: ClientCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_info: Option<Implementation>,
+}
+
+impl AcpRequest for InitializeRequest {
+    const METHOD: &'static str = "initialize";
+    type Response = InitializeResponse;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fs: Option<FileSystemCapability>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub terminal: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSystemCapability {
+    #[serde(default)]
+    pub read_text_file: bool,
+    #[serde(default)]
+    pub write_text_file: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Implementation {
+    pub name: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResponse {
+    pub protocol_version: u32,
+    #[serde(default)]
+    pub agent_capabilities: AgentCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_info: Option<Implementation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth_methods: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCapabilities {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub load_session: bool,
+    #[serde(default)]
+    pub prompt_capabilities: PromptCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_capabilities: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptCapabilities {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub image: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub audio: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub embedded_context: bool,
+}
+
+// Session lifecycle: creating, loading, configuring, prompting and cancelling
+
+/// A stdio MCP server the agent should start for the session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct McpServer {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: Vec<EnvVariable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnvVariable {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionRequest {
+    pub cwd: String,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+impl AcpRequest for NewSessionRequest {
+    const METHOD: &'static str = "session/new";
+    type Response = NewSessionResponse;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewSessionResponse {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<SessionModeState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<SessionModelState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSessionRequest {
+    pub session_id: String,
+    pub cwd: String,
+    pub mcp_servers: Vec<McpServer>,
+}
+
+impl AcpRequest for LoadSessionRequest {
+    const METHOD: &'static str = "session/load";
+    type Response = LoadSessionResponse;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadSessionResponse {
+    /// Kiro echoes the id back; the spec does not require it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<SessionModeState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<SessionModelState>,
+}
+
+/// The agent modes a session can switch between (`modes` in session responses).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionModeState {
+    pub current_mode_id: String,
+    #[serde(default)]
+    pub available_modes: Vec<SessionMode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionMode {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The models a session can use (`models` in session responses).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionModelState {
+    pub current_model_id: String,
+    #[serde(default)]
+    pub available_models: Vec<ModelInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub model_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSessionModelRequest {
+    pub session_id: String,
+    pub model_id: String,
+}
+
+impl AcpRequest for SetSessionModelRequest {
+    const METHOD: &'static str = "session/set_model";
+    type Response = Value;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSessionModeRequest {
+    pub session_id: String,
+    pub mode_id: String,
+}
+
+impl AcpRequest for SetSessionModeRequest {
+    const METHOD: &'static str = "session/set_mode";
+    type Response = Value;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptRequest {
+    pub session_id: String,
+    pub prompt: Vec<ContentBlock>,
+}
+
+impl AcpRequest for PromptRequest {
+    const METHOD: &'static str = "session/prompt";
+    type Response = PromptResponse;
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptResponse {
+    pub stop_reason: StopReason,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Value>,
+    #[serde(default, rename = "_meta", skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+}
+
+/// Why the agent ended a prompt turn.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    EndTurn,
+    MaxTokens,
+    MaxTurnRequests,
+    Refusal,
+    Cancelled,
+}
+
+/// `session/cancel` is a notification: the prompt ends with `StopReason::Cancelled`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelNotification {
+    pub session_id: String,
+}
+
+impl CancelNotification {
+    pub const METHOD: &'static str = "session/cancel";
+}
+
+// Content blocks sent in prompts and streamed back in updates
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    Text(TextContent),
+    Image(ImageContent),
+    Audio(AudioContent),
+    ResourceLink(ResourceLink),
+    Resource(EmbeddedResource),
+}
+
+impl ContentBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentBlock::Text(TextContent { text: text.into(), annotations: None })
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ContentBlock::Text(t) => Some(&t.text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextContent {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageContent {
+    /// Base64-encoded image bytes
+    pub data: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioContent {
+    pub data: String,
+    pub mime_type: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLink {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EmbeddedResource {
+    pub resource: ResourceContents,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ResourceContents {
+    Text(TextResourceContents),
+    Blob(BlobResourceContents),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub blob: String,
+}
+
+/// Chunks sometimes arrive as a bare `{"text": ...}`; treat those as text blocks.
+fn lenient_content<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ContentBlock, D::Error> {
+    let mut value = Value::deserialize(deserializer)?;
+    if let Some(obj) = value.as_object_mut() {
+        if !obj.contains_key("type") && obj.contains_key("text") {
+            obj.insert("type".to_string(), Value::from("text"));
+        }
+    }
+    serde_json::from_value(value).map_err(serde::de::Error::custom)
+}
+
+// `session/update` notifications
+
+pub const SESSION_UPDATE: &str = "session/update";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionNotification {
+    /// Optional so the abbreviated examples in the reference doc still parse
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub update: SessionUpdate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "sessionUpdate", rename_all = "snake_case")]
+pub enum SessionUpdate {
+    UserMessageChunk {
+        #[serde(deserialize_with = "lenient_content")]
+        content: ContentBlock,
+    },
+    AgentMessageChunk {
+        #[serde(deserialize_with = "lenient_content")]
+        content: ContentBlock,
+    },
+    AgentThoughtChunk {
+        #[serde(deserialize_with = "lenient_content")]
+        content: ContentBlock,
+    },
+    ToolCall(ToolCall),
+    ToolCallUpdate(ToolCallUpdate),
+    Plan(Plan),
+    #[serde(rename_all = "camelCase")]
+    AvailableCommandsUpdate {
+        available_commands: Vec<AvailableCommand>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CurrentModeUpdate {
+        current_mode_id: String,
+    },
+    /// Variants this client does not know yet (`turn_end`, agent extensions, ...)
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Read,
+    Edit,
+    Delete,
+    Move,
+    Search,
+    Execute,
+    Think,
+    Fetch,
+    SwitchMode,
+    #[default]
+    #[serde(other)]
+    Other,
+}
+
+impl ToolKind {
+    /// The wire name, used as the key for permission policies
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolKind::Read => "read",
+            ToolKind::Edit => "edit",
+            ToolKind::Delete => "delete",
+            ToolKind::Move => "move",
+            ToolKind::Search => "search",
+            ToolKind::Execute => "execute",
+            ToolKind::Think => "think",
+            ToolKind::Fetch => "fetch",
+            ToolKind::SwitchMode => "switch_mode",
+            ToolKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    #[default]
+    Pending,
+    InProgress,
+    Completed,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub tool_call_id: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub kind: ToolKind,
+    #[serde(default)]
+    pub status: ToolCallStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content: Vec<ToolCallContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<ToolCallLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<Value>,
+}
+
+/// Changes to an existing tool call; only the fields that changed are set.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallUpdate {
+    pub tool_call_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ToolKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ToolCallStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<ToolCallContent>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locations: Option<Vec<ToolCallLocation>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_input: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_output: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum ToolCallContent {
+    Content {
+        content: ContentBlock,
+    },
+    Diff {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        old_text: Option<String>,
+        new_text: String,
+    },
+    Terminal {
+        terminal_id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ToolCallLocation {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub entries: Vec<PlanEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlanEntry {
+    pub content: String,
+    pub priority: PlanEntryPriority,
+    pub status: PlanEntryStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanEntryPriority {
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanEntryStatus {
+    Pending,
+    InProgress,
+    Completed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AvailableCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<AvailableCommandInput>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AvailableCommandInput {
+    pub hint: String,
+}
+
+// Requests the agent sends to the client
+
+pub const REQUEST_PERMISSION: &str = "session/request_permission";
+pub const READ_TEXT_FILE: &str = "fs/read_text_file";
+pub const WRITE_TEXT_FILE: &str = "fs/write_text_file";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPermissionRequest {
+    pub session_id: String,
+    pub tool_call: ToolCallUpdate,
+    pub options: Vec<PermissionOption>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionOption {
+    pub option_id: String,
+    pub name: String,
+    pub kind: PermissionOptionKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionOptionKind {
+    AllowOnce,
+    AllowAlways,
+    RejectOnce,
+    RejectAlways,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestPermissionResponse {
+    pub outcome: RequestPermissionOutcome,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RequestPermissionOutcome {
+    Cancelled,
+    #[serde(rename_all = "camelCase")]
+    Selected { option_id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadTextFileRequest {
+    pub session_id: String,
+    pub path: String,
+    /// 1-based line to start from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// Maximum number of lines to return
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReadTextFileResponse {
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteTextFileRequest {
+    pub session_id: String,
+    pub path: String,
+    pub content: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Parse `json` as `T`, serialize it back and require the same JSON.
+    fn round_trip<T: Serialize + DeserializeOwned>(json: Value) -> T {
+        let parsed: T = serde_json::from_value(json.clone()).expect("parse");
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+        parsed
+    }
+
+    #[test]
+    fn initialize_request() {
+        let req: Request<InitializeRequest> = round_trip(json!({"jsonrpc":"2.0","id":0,"method":"initialize","params":{
+            "protocolVersion":1,
+            "clientCapabilities":{},
+            "clientInfo":{"name":"my-app","version":"0.1.0"}
+        }}));
+        assert_eq!(req.id, RequestId::Number(0));
+        assert_eq!(req.method, InitializeRequest::METHOD);
+        assert_eq!(req.params.client_info.unwrap().name, "my-app");
+    }
+
+    #[test]
+    fn initialize_request_with_fs_capabilities() {
+        let req: InitializeRequest = round_trip(json!({
+            "protocolVersion":1,
+            "clientCapabilities":{"fs":{"readTextFile":true,"writeTextFile":false}}
+        }));
+        assert!(req.client_capabilities.fs.unwrap().read_text_file);
+    }
+
+    #[test]
+    fn initialize_response() {
+        let res: Response<InitializeResponse> = round_trip(json!({"jsonrpc":"2.0","id":0,"result":{
+            "protocolVersion":1,
+            "agentCapabilities":{"loadSession":true,"promptCapabilities":{"image":true}},
+            "agentInfo":{"name":"Kiro Agent","version":"1.26.2"}
+        }}));
+        let ResponsePayload::Result(result) = res.payload else { panic!("expected result") };
+        assert!(result.agent_capabilities.load_session);
+        assert!(result.agent_capabilities.prompt_capabilities.image);
+        assert!(!result.agent_capabilities.prompt_capabilities.embedded_context);
+        assert_eq!(result.agent_info.unwrap().version, "1.26.2");
+    }
+
+    #[test]
+    fn new_session() {
+        let req: Request<NewSessionRequest> = round_trip(json!({"jsonrpc":"2.0","id":1,"method":"session/new","params":{
+            "cwd":"/path/to/project",
+            "mcpServers":[]
+        }}));
+        assert_eq!(req.method, NewSessionRequest::METHOD);
+        let res: Response<NewSessionResponse> = round_trip(json!({"jsonrpc":"2.0","id":1,"result":{"sessionId":"uuid-here"}}));
+        assert_eq!(res.payload, ResponsePayload::Result(NewSessionResponse {
+            session_id: "uuid-here".to_string(), modes: None, models: None,
+        }));
+    }
+
+    #[test]
+    fn new_session_with_mcp_server() {
+        let req: NewSessionRequest = round_trip(json!({
+            "cwd":"/ws",
+            "mcpServers":[{"name":"files","command":"/app","args":["--mcp-server","/ws"],"env":[{"name":"A","value":"1"}]}]
+        }));
+        assert_eq!(req.mcp_servers[0].args, vec!["--mcp-server", "/ws"]);
+    }
+
+    #[test]
+    fn prompt_request_uses_prompt_not_content() {
+        let req: Request<PromptRequest> = round_trip(json!({"jsonrpc":"2.0","id":2,"method":"session/prompt","params":{
+            "sessionId":"uuid-here",
+            "prompt":[{"type":"text","text":"hello"}]
+        }}));
+        assert_eq!(req.params.prompt, vec![ContentBlock::text("hello")]);
+        assert!(serde_json::from_value::<PromptRequest>(json!({
+            "sessionId":"uuid-here", "content":[{"type":"text","text":"hello"}]
+        })).is_err());
+    }
+
+    #[test]
+    fn streaming_chunk_from_reference() {
+        // The reference abbreviates the chunk content without a `type`
+        let msg: Notification<SessionNotification> = serde_json::from_value(json!({"jsonrpc":"2.0","method":"session/update","params":{
+            "update":{"sessionUpdate":"agent_message_chunk","content":{"text":"chunk..."}}
+        }})).unwrap();
+        assert_eq!(msg.method, SESSION_UPDATE);
+        assert_eq!(msg.params.update, SessionUpdate::AgentMessageChunk { content: ContentBlock::text("chunk...") });
+
+        round_trip::<Notification<SessionNotification>>(json!({"jsonrpc":"2.0","method":"session/update","params":{
+            "sessionId":"uuid-here",
+            "update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"chunk..."}}
+        }}));
+    }
+
+    #[test]
+    fn prompt_response() {
+        let res: Response<PromptResponse> = round_trip(json!({"jsonrpc":"2.0","id":2,"result":{"stopReason":"end_turn"}}));
+        let ResponsePayload::Result(result) = res.payload else { panic!("expected result") };
+        assert_eq!(result.stop_reason, StopReason::EndTurn);
+
+        let result: PromptResponse = round_trip(json!({
+            "stopReason":"cancelled", "usage":{"outputTokens":3}, "_meta":{"credits":0.1}
+        }));
+        assert_eq!(result.stop_reason, StopReason::Cancelled);
+        assert_eq!(result.meta, Some(json!({"credits":0.1})));
+    }
+
+    #[test]
+    fn load_session() {
+        let req: Request<LoadSessionRequest> = round_trip(json!({"jsonrpc":"2.0","id":1,"method":"session/load","params":{
+            "sessionId":"uuid-here",
+            "cwd":"/path/to/project",
+            "mcpServers":[]
+        }}));
+        assert_eq!(req.method, LoadSessionRequest::METHOD);
+        let res: Response<LoadSessionResponse> = round_trip(json!({"jsonrpc":"2.0","id":1,"result":{
+            "sessionId":"uuid-here",
+            "modes":{"currentModeId":"default","availableModes":[]}
+        }}));
+        let ResponsePayload::Result(result) = res.payload else { panic!("expected result") };
+        assert_eq!(result.session_id.as_deref(), Some("uuid-here"));
+        assert_eq!(result.modes.unwrap().current_mode_id, "default");
+    }
+
+    #[test]
+    fn session_models_and_modes() {
+        let res: NewSessionResponse = round_trip(json!({
+            "sessionId":"uuid-here",
+            "modes":{"currentModeId":"default","availableModes":[
+                {"id":"default","name":"Default"},
+                {"id":"planner","name":"Planner","description":"Plans before acting"}
+            ]},
+            "models":{"currentModelId":"auto","availableModels":[
+                {"modelId":"auto","name":"Auto","description":"1.00x credits"},
+                {"modelId":"claude-sonnet-4","name":"Sonnet 4"}
+            ]}
+        }));
+        let modes = res.modes.unwrap();
+        assert_eq!(modes.available_modes[1].description.as_deref(), Some("Plans before acting"));
+        let models = res.models.unwrap();
+        assert_eq!(models.current_model_id, "auto");
+        assert_eq!(models.available_models[1].model_id, "claude-sonnet-4");
+    }
+
+    #[test]
+    fn set_mode_uses_mode_id() {
+        let req: Request<SetSessionModeRequest> = round_trip(json!({"jsonrpc":"2.0","id":4,"method":"session/set_mode","params":{
+            "sessionId":"uuid-here",
+            "modeId":"planner"
+        }}));
+        assert_eq!(req.method, SetSessionModeRequest::METHOD);
+        assert_eq!(req.params.mode_id, "planner");
+    }
+
+    #[test]
+    fn set_model_uses_model_id() {
+        let req: Request<SetSessionModelRequest> = round_trip(json!({"jsonrpc":"2.0","id":3,"method":"session/set_model","params":{
+            "sessionId":"uuid-here",
+            "modelId":"claude-sonnet-4"
+        }}));
+        assert_eq!(req.params.model_id, "claude-sonnet-4");
+        assert!(serde_json::from_value::<SetSessionModelRequest>(json!({
+            "sessionId":"uuid-here", "model":"claude-sonnet-4"
+        })).is_err());
+    }
+
+    #[test]
+    fn cancel_is_a_notification() {
+        let msg = Notification::new(CancelNotification::METHOD, CancelNotification { session_id: "s".to_string() });
+        assert_eq!(serde_json::to_value(&msg).unwrap(), json!({
+            "jsonrpc":"2.0","method":"session/cancel","params":{"sessionId":"s"}
+        }));
+    }
+
+    #[test]
+    fn error_response() {
+        let res: Response<Value> = round_trip(json!({"jsonrpc":"2.0","id":1,"error":{
+            "code":-32603,"message":"Session is active in another process (PID 42)"
+        }}));
+        let ResponsePayload::Error(error) = res.payload else { panic!("expected error") };
+        assert_eq!(error.code, RpcError::INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn tool_call_updates() {
+        let update: SessionUpdate = round_trip(json!({
+            "sessionUpdate":"tool_call","toolCallId":"c1","title":"Read notes","kind":"read","status":"pending",
+            "locations":[{"path":"/ws/notes.md","line":3}],"rawInput":{"path":"notes.md"}
+        }));
+        let SessionUpdate::ToolCall(call) = update else { panic!("expected tool_call") };
+        assert_eq!(call.kind, ToolKind::Read);
+
+        let update: SessionUpdate = round_trip(json!({
+            "sessionUpdate":"tool_call_update","toolCallId":"c1","status":"completed",
+            "content":[
+                {"type":"content","content":{"type":"text","text":"ok"}},
+                {"type":"diff","path":"/ws/a.md","oldText":"a","newText":"b"},
+                {"type":"terminal","terminalId":"t1"}
+            ]
+        }));
+        let SessionUpdate::ToolCallUpdate(update) = update else { panic!("expected tool_call_update") };
+        assert_eq!(update.status, Some(ToolCallStatus::Completed));
+        assert!(update.title.is_none());
+    }
+
+    #[test]
+    fn unknown_tool_kind_falls_back_to_other() {
+        let call: ToolCall = serde_json::from_value(json!({"toolCallId":"c1","kind":"teleport"})).unwrap();
+        assert_eq!(call.kind, ToolKind::Other);
+        assert_eq!(call.status, ToolCallStatus::Pending);
+    }
+
+    #[test]
+    fn plan_commands_and_modes() {
+        round_trip::<SessionUpdate>(json!({"sessionUpdate":"plan","entries":[
+            {"content":"Read","priority":"high","status":"in_progress"}
+        ]}));
+        round_trip::<SessionUpdate>(json!({"sessionUpdate":"available_commands_update","availableCommands":[
+            {"name":"compact","description":"Compact","input":{"hint":"how"}}
+        ]}));
+        round_trip::<SessionUpdate>(json!({"sessionUpdate":"current_mode_update","currentModeId":"planner"}));
+    }
+
+    #[test]
+    fn unknown_session_update() {
+        let update: SessionUpdate = serde_json::from_value(json!({"sessionUpdate":"turn_end"})).unwrap();
+        assert_eq!(update, SessionUpdate::Unknown);
+    }
+
+    #[test]
+    fn content_blocks() {
+        round_trip::<ContentBlock>(json!({"type":"image","data":"aGk=","mimeType":"image/png"}));
+        round_trip::<ContentBlock>(json!({"type":"resource_link","uri":"file:///ws/a.pdf","name":"a.pdf","mimeType":"application/pdf"}));
+        let block: ContentBlock = round_trip(json!({"type":"resource","resource":{
+            "uri":"file:///ws/a.md","mimeType":"text/markdown","text":"# A"
+        }}));
+        assert!(matches!(block, ContentBlock::Resource(EmbeddedResource { resource: ResourceContents::Text(_) })));
+        let block: ContentBlock = round_trip(json!({"type":"resource","resource":{"uri":"file:///ws/a.bin","blob":"AA=="}}));
+        assert!(matches!(block, ContentBlock::Resource(EmbeddedResource { resource: ResourceContents::Blob(_) })));
+    }
+
+    #[test]
+    fn permission_request_and_response() {
+        let req: Request<RequestPermissionRequest> = round_trip(json!({"jsonrpc":"2.0","id":"perm-1","method":"session/request_permission","params":{
+            "sessionId":"s",
+            "toolCall":{"toolCallId":"c1","title":"Run ls","kind":"execute"},
+            "options":[
+                {"optionId":"a","name":"Allow","kind":"allow_once"},
+                {"optionId":"r","name":"Reject","kind":"reject_always"}
+            ]
+        }}));
+        assert_eq!(req.id, RequestId::String("perm-1".to_string()));
+        assert_eq!(req.params.options[1].kind, PermissionOptionKind::RejectAlways);
+
+        round_trip::<RequestPermissionResponse>(json!({"outcome":{"outcome":"selected","optionId":"a"}}));
+        round_trip::<RequestPermissionResponse>(json!({"outcome":{"outcome":"cancelled"}}));
+    }
+
+    #[test]
+    fn fs_requests() {
+        let req: ReadTextFileRequest = round_trip(json!({"sessionId":"s","path":"/ws/a.pdf","line":2,"limit":10}));
+        assert_eq!(req.line, Some(2));
+        round_trip::<WriteTextFileRequest>(json!({"sessionId":"s","path":"/ws/n.md","content":"hi"}));
+    }
+
+    #[test]
+    fn incoming_message_classification() {
+        let msg: IncomingMessage = serde_json::from_value(json!({
+            "jsonrpc":"2.0","id":5,"method":"fs/read_text_file","params":{"sessionId":"s","path":"/a"}
+        })).unwrap();
+        assert_eq!(msg.id, Some(RequestId::Number(5)));
+        assert_eq!(msg.session_id(), Some("s"));
+        let params: ReadTextFileRequest = msg.parse_params().unwrap();
+        assert_eq!(params.path, "/a");
+    }
+}
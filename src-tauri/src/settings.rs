@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::acp::Timeouts;
+use crate::agents::AgentProfile;
+use crate::error::AppError;
+
+/// Warm agent processes kept ready for new tabs by default
+pub const DEFAULT_POOL_SIZE: usize = 1;
+/// Agent processes allowed at once by default, warm ones included
+pub const DEFAULT_MAX_LIVE_PROCESSES: usize = 6;
+
+/// App settings, persisted as JSON in the user's config directory.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub kiro_cli: KiroCliSettings,
+    /// Other ACP agents the user added, offered next to the built-in Kiro profile
+    pub agents: Vec<AgentProfile>,
+    /// Profile id new sessions use when none is picked; Kiro when unset
+    pub default_agent: Option<String>,
+    /// Pre-initialized processes kept for new tabs; 0 turns the pool off
+    pub pool_size: usize,
+    /// Once this many agent processes run, the least recently used idle session is
+    /// suspended and reloaded on its next use; 0 means no limit
+    pub max_live_processes: usize,
+    pub timeouts: Timeouts,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            kiro_cli: KiroCliSettings::default(),
+            agents: Vec::new(),
+            default_agent: None,
+            pool_size: DEFAULT_POOL_SIZE,
+            max_live_processes: DEFAULT_MAX_LIVE_PROCESSES,
+            timeouts: Timeouts::default(),
+        }
+    }
+}
+
+/// How `kiro-cli` is found and started. Everything is optional; the defaults run
+/// `kiro-cli acp` from the usual install locations in the workspace.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct KiroCliSettings {
+    /// Binary to run instead of searching for one
+    pub path: Option<String>,
+    /// Appended after `acp`, e.g. `--agent <name>` or `--trust-all-tools`
+    pub args: Vec<String>,
+    /// Added to the app's environment, e.g. `KIRO_LOG_LEVEL=debug`
+    pub env: HashMap<String, String>,
+    /// Directory the process starts in; the workspace when unset
+    pub working_dir: Option<String>,
+}
+
+fn log(msg: &str) {
+    eprintln!("[SETTINGS] {}", msg);
+}
+
+/// `<config dir>/kiro-notebook/settings.json`
+pub fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("kiro-notebook").join("settings.json"))
+}
+
+/// The saved settings, or the defaults when there are none or they cannot be read.
+pub fn load() -> Settings {
+    let Some(path) = settings_path() else { return Settings::default() };
+    if !path.exists() {
+        return Settings::default();
+    }
+    load_from(&path).unwrap_or_else(|e| {
+        log(&e.to_string());
+        Settings::default()
+    })
+}
+
+pub fn save(settings: &Settings) -> Result<(), AppError> {
+    save_to(&settings_path().ok_or_else(|| AppError::NoUserDirectory { name: "config".to_string() })?, settings)
+}
+
+pub fn load_from(path: &Path) -> Result<Settings, AppError> {
+    let content = fs::read_to_string(path).map_err(|e| AppError::io(path, e))?;
+    serde_json::from_str(&content).map_err(|e| AppError::io(path, e))
+}
+
+pub fn save_to(path: &Path, settings: &Settings) -> Result<(), AppError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| AppError::io(dir, e))?;
+    }
+    let content = serde_json::to_string_pretty(settings).map_err(|e| AppError::io(path, e))?;
+    fs::write(path, content).map_err(|e| AppError::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("settings.json");
+        let settings = Settings {
+            kiro_cli: KiroCliSettings {
+                path: Some("/opt/kiro/kiro-cli".to_string()),
+                args: vec!["--agent".to_string(), "notebook".to_string()],
+                env: HashMap::from([("KIRO_LOG_LEVEL".to_string(), "debug".to_string())]),
+                working_dir: None,
+            },
+            agents: vec![AgentProfile {
+                id: "gemini".to_string(),
+                name: "Gemini CLI".to_string(),
+                command: "gemini".to_string(),
+                args: vec!["--experimental-acp".to_string()],
+                ..AgentProfile::default()
+            }],
+            default_agent: Some("gemini".to_string()),
+            pool_size: 0,
+            max_live_processes: 3,
+            timeouts: Timeouts { prompt_inactivity_secs: 0, ..Timeouts::default() },
+        };
+        save_to(&path, &settings).unwrap();
+        assert_eq!(load_from(&path).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let settings: Settings = serde_json::from_value(json!({ "kiroCli": { "args": ["--verbose"] } })).unwrap();
+        assert_eq!(settings.kiro_cli.args, vec!["--verbose"]);
+        assert_eq!(settings.kiro_cli.path, None);
+        assert!(settings.kiro_cli.env.is_empty());
+        assert_eq!(settings.pool_size, DEFAULT_POOL_SIZE);
+        assert_eq!(serde_json::from_value::<Settings>(json!({})).unwrap(), Settings::default());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::sync::Mutex;
+
+use crate::acp::{AcpClient, EventSink, Timeouts};
+use crate::agents::AgentProfile;
+use crate::commands;
+use crate::error::AppError;
+use crate::events::{CrashedEvent, RestartEvent};
+use crate::AppState;
+
+/// Attempts at reloading a crashed session before giving up
+const MAX_RESTART_ATTEMPTS: u32 = 3;
+/// Wait before the next attempt, multiplied by the attempt number
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+
+fn log(msg: &str) {
+    eprintln!("[SUPERVISOR] {}", msg);
+}
+
+/// Watches the process behind `session_id`. When it exits on its own, `acp-crashed` is
+/// emitted with its stderr, and the session is reloaded into a fresh process that
+/// replaces the dead client in `sessions`, under the same live process cap as new
+/// sessions. Sessions closed in the meantime are left alone.
+pub fn watch(
+    app: AppHandle,
+    state: AppState,
+    profile: AgentProfile,
+    session_id: String,
+    cwd: String,
+    client: Arc<AcpClient>,
+) {
+    tokio::spawn(async move {
+        let AppState { sessions, interrupted_prompts, .. } = &state;
+        let mut client = client;
+        loop {
+            if !client.wait_exit().await || !is_current(sessions, &session_id, &client).await {
+                return;
+            }
+            let interrupted_prompt = interrupted_prompts.lock().await.contains_key(&session_id);
+            log(&format!("Session {} crashed ({})", session_id, client.exit_status().unwrap_or_else(|| "stdout closed".to_string())));
+            app.emit_event("acp-crashed", serde_json::to_value(CrashedEvent {
+                session_id: session_id.clone(),
+                exit_status: client.exit_status(),
+                stderr: client.stderr_tail(),
+                trace_path: client.diagnostics().trace_path,
+                interrupted_prompt,
+            }).unwrap_or_default());
+            client.kill();
+
+            let restarted = match restart(&session_id, &cwd, &profile, client.timeouts(), &state).await {
+                Ok(restarted) => Arc::new(restarted),
+                Err(error) => {
+                    interrupted_prompts.lock().await.remove(&session_id);
+                    app.emit_event("acp-restart-failed", serde_json::to_value(RestartEvent {
+                        session_id: session_id.clone(),
+                        interrupted_prompt,
+                        error: Some(error),
+                    }).unwrap_or_default());
+                    return;
+                }
+            };
+            {
+                let mut sessions = sessions.lock().await;
+                // Closed while restarting: the new process goes away with `restarted`
+                if !matches!(sessions.get(&session_id), Some(current) if Arc::ptr_eq(current, &client)) {
+                    return;
+                }
+                sessions.insert(session_id.clone(), restarted.clone());
+            }
+            log(&format!("Session {} restarted", session_id));
+            app.emit_event("acp-restarted", serde_json::to_value(RestartEvent {
+                session_id: session_id.clone(),
+                interrupted_prompt,
+                error: None,
+            }).unwrap_or_default());
+            client = restarted;
+        }
+    });
+}
+
+/// Whether `client` still serves `session_id`, i.e. the tab was not closed or replaced.
+async fn is_current(sessions: &Mutex<HashMap<String, Arc<AcpClient>>>, session_id: &str, client: &Arc<AcpClient>) -> bool {
+    matches!(sessions.lock().await.get(session_id), Some(current) if Arc::ptr_eq(current, client))
+}
+
+async fn restart(session_id: &str, cwd: &str, profile: &AgentProfile, timeouts: Timeouts, state: &AppState) -> Result<AcpClient, AppError> {
+    let mut attempt = 1;
+    loop {
+        commands::make_room(state).await;
+        match commands::spawn_and_load(session_id, cwd, profile, timeouts).await {
+            Ok(client) => return Ok(client),
+            Err(e) if attempt < MAX_RESTART_ATTEMPTS => {
+                log(&format!("Restart attempt {} for {} failed: {}", attempt, session_id, e));
+                tokio::time::sleep(RESTART_BACKOFF * attempt).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+
+/// Where traces go, relative to the workspace root.
+pub const LOGS_DIR: &str = ".kiro-notebook/logs";
+/// Traces kept per workspace; older ones are removed when a new one starts
+const MAX_TRACES: usize = 20;
+
+/// Which way a message went, seen from this client.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Client to agent
+    Out,
+    /// Agent to client
+    In,
+}
+
+/// One line of a trace file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub time: String,
+    pub direction: Direction,
+    pub message: Value,
+}
+
+/// Records every JSON-RPC message exchanged with one agent process as JSON lines.
+pub struct Trace {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl Trace {
+    /// Start a trace under `<workspace>/.kiro-notebook/logs`, named after the time and `pid`.
+    pub fn create(workspace: &Path, pid: u32) -> Result<Self, AppError> {
+        let dir = workspace.join(LOGS_DIR);
+        fs::create_dir_all(&dir).map_err(|e| AppError::io(&dir, e))?;
+        prune(&dir);
+        let name = format!("acp-{}-{}.jsonl", chrono::Local::now().format("%Y%m%d-%H%M%S"), pid);
+        let path = dir.join(name);
+        let file = File::create(&path).map_err(|e| AppError::io(&path, e))?;
+        Ok(Self { path, file: Mutex::new(file) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, direction: Direction, message: &Value) {
+        let entry = TraceEntry {
+            time: chrono::Local::now().to_rfc3339(),
+            direction,
+            message: message.clone(),
+        };
+        let Ok(line) = serde_json::to_string(&entry) else { return };
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+/// Read a trace back, skipping lines that do not parse (e.g. one cut off by a crash).
+pub fn read(path: &Path) -> Result<Vec<TraceEntry>, AppError> {
+    let file = File::open(path).map_err(|e| AppError::from_io(path, e))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Keep the newest `MAX_TRACES - 1` traces, making room for the one about to start.
+fn prune(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut traces: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+        .collect();
+    if traces.len() < MAX_TRACES {
+        return;
+    }
+    // Names start with the timestamp, so they sort oldest first
+    traces.sort();
+    for old in &traces[..traces.len() + 1 - MAX_TRACES] {
+        let _ = fs::remove_file(old);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn records_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let trace = Trace::create(dir.path(), 42).unwrap();
+        assert!(trace.path().starts_with(dir.path().join(LOGS_DIR)));
+        trace.record(Direction::Out, &json!({"jsonrpc":"2.0","id":0,"method":"initialize","params":{}}));
+        trace.record(Direction::In, &json!({"jsonrpc":"2.0","id":0,"result":{}}));
+
+        let entries = read(trace.path()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::Out);
+        assert_eq!(entries[1].message, json!({"jsonrpc":"2.0","id":0,"result":{}}));
+    }
+
+    #[test]
+    fn old_traces_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = dir.path().join(LOGS_DIR);
+        fs::create_dir_all(&logs).unwrap();
+        for i in 0..MAX_TRACES + 5 {
+            fs::write(logs.join(format!("acp-20260101-0000{:02}-1.jsonl", i)), "").unwrap();
+        }
+        Trace::create(dir.path(), 1).unwrap();
+        assert_eq!(fs::read_dir(&logs).unwrap().count(), MAX_TRACES);
+        assert!(!logs.join("acp-20260101-000000-1.jsonl").exists());
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+/// Resolves a requested path against the workspace root. Symlinks and `..` are resolved
+/// first, so anything that ends up outside the root is rejected.
+pub fn resolve_in_workspace(root: &Path, requested: &str) -> Result<PathBuf, AppError> {
+    if requested.is_empty() {
+        return Err(AppError::InvalidArgument { name: "path".to_string(), message: "required".to_string() });
+    }
+    let path = Path::new(requested);
+    let joined = if path.is_absolute() { path.to_path_buf() } else { root.join(path) };
+    let canonical = std::fs::canonicalize(&joined).map_err(|e| AppError::from_io(path, e))?;
+    if !canonical.starts_with(root) {
+        return Err(AppError::AccessDenied { path: requested.to_string() });
+    }
+    Ok(canonical)
+}
@@ -0,0 +1,139 @@
+use std::io::{Cursor, Read, Seek};
+use xml::reader::{EventReader, XmlEvent};
+
+/// An XML element with namespace prefixes dropped, so `w:p` is `p` and `r:id` is `id`.
+/// Enough to read the parts of Office and EPUB files.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// The root element of `xml`.
+pub fn parse(xml: &[u8]) -> Result<Element, String> {
+    // Elements still open; the root ends up alone at the bottom
+    let mut open: Vec<Element> = Vec::new();
+    for event in EventReader::new(xml) {
+        match event.map_err(|e| format!("Invalid XML: {}", e))? {
+            XmlEvent::StartElement { name, attributes, .. } => open.push(Element {
+                name: name.local_name,
+                attributes: attributes.into_iter().map(|a| (a.name.local_name, a.value)).collect(),
+                children: Vec::new(),
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = open.pop().ok_or("Invalid XML: unbalanced end tag")?;
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(Node::Element(element)),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) | XmlEvent::Whitespace(text) => {
+                if let Some(parent) = open.last_mut() {
+                    parent.children.push(Node::Text(text));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("Invalid XML: no root element".to_string())
+}
+
+/// The file at `path` in a zip package such as a `.docx` or `.epub`, or `None` when
+/// there is none.
+pub fn zip_entry<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, path: &str) -> Result<Option<Vec<u8>>, String> {
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Some(bytes))
+}
+
+/// The XML part at `path` in a zip package, or `None` when there is none.
+pub fn zip_part<R: Read + Seek>(archive: &mut zip::ZipArchive<R>, path: &str) -> Result<Option<Element>, String> {
+    match zip_entry(archive, path)? {
+        Some(bytes) => parse(&bytes).map(Some).map_err(|e| format!("{} in {}", e, path)),
+        None => Ok(None),
+    }
+}
+
+/// Open `bytes` as a zip package; `kind` names the format in the error.
+pub fn open_zip<'a>(bytes: &'a [u8], kind: &str) -> Result<zip::ZipArchive<Cursor<&'a [u8]>>, String> {
+    zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("not a {} file: {}", kind, e))
+}
+
+/// A zip with `files` as `(path, content)`, for building fixtures in tests.
+#[cfg(test)]
+pub fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, content) in files {
+        zip.start_file(*path, zip::write::FileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|child| match child {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |element| element.name == name)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// All text below this element.
+    pub fn text(&self) -> String {
+        self.children.iter().map(|child| match child {
+            Node::Element(element) => element.text(),
+            Node::Text(text) => text.clone(),
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_are_dropped() {
+        let root = parse(br#"<?xml version="1.0"?>
+<w:document xmlns:w="urn:w" xmlns:r="urn:r"><w:body><w:hyperlink r:id="rId4"><w:t xml:space="preserve"> a &amp; b</w:t></w:hyperlink><w:t><![CDATA[<c>]]></w:t></w:body></w:document>"#).unwrap();
+        assert_eq!(root.name, "document");
+        let body = root.child("body").unwrap();
+        assert_eq!(body.child("hyperlink").unwrap().attr("id"), Some("rId4"));
+        assert_eq!(body.text(), " a & b<c>");
+        assert!(parse(b"<a><b></a>").is_err());
+    }
+
+    #[test]
+    fn zip_parts_are_found_by_path() {
+        let bytes = zip(&[("a/b.xml", "<b>text</b>"), ("bad.xml", "<b>")]);
+        let mut archive = open_zip(&bytes, "test").unwrap();
+        assert_eq!(zip_part(&mut archive, "a/b.xml").unwrap().unwrap().text(), "text");
+        assert_eq!(zip_part(&mut archive, "missing.xml").unwrap(), None);
+        assert!(zip_part(&mut archive, "bad.xml").unwrap_err().contains("bad.xml"));
+        assert!(open_zip(b"not a zip", "test").is_err());
+    }
+}
@@ -0,0 +1,680 @@
+//! Drives `AcpClient` against the scripted mock agent in `tests/support`.
+//! Run with `cargo test --features mock-agent`.
+#![cfg(feature = "mock-agent")]
+
+use kiro_notebook_lib::acp::{AcpClient, AgentCommand, EventSink, PermissionPolicy, StopReason, Timeouts};
+use kiro_notebook_lib::error::AppError;
+use kiro_notebook_lib::protocol::ContentBlock;
+use kiro_notebook_lib::trace::{self, Direction};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const SESSION: &str = "sess-1";
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(String, Value)>>);
+
+impl EventSink for Recorder {
+    fn emit_event(&self, event: &str, payload: Value) {
+        self.0.lock().unwrap().push((event.to_string(), payload));
+    }
+}
+
+impl Recorder {
+    fn named(&self, event: &str) -> Vec<Value> {
+        self.0.lock().unwrap().iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+}
+
+fn write_script(dir: &tempfile::TempDir, steps: Vec<Value>) {
+    std::fs::write(dir.path().join("mock-acp-script.json"), Value::Array(steps).to_string()).unwrap();
+}
+
+/// A workspace directory holding the script the mock agent will replay.
+fn workspace(steps: Vec<Value>) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    write_script(&dir, steps);
+    dir
+}
+
+fn spawn(dir: &tempfile::TempDir) -> AcpClient {
+    AcpClient::spawn(env!("CARGO_BIN_EXE_mock-acp-agent"), dir.path().to_str().unwrap()).unwrap()
+}
+
+fn handshake() -> Vec<Value> {
+    vec![
+        json!({ "expect": "initialize" }),
+        json!({ "respond": {
+            "protocolVersion": 1,
+            "agentCapabilities": { "loadSession": true, "promptCapabilities": { "image": true } },
+            "agentInfo": { "name": "Mock Agent", "version": "0.0.1" }
+        } }),
+        json!({ "expect": "session/new" }),
+        json!({ "respond": { "sessionId": SESSION } }),
+    ]
+}
+
+fn chunk(text: &str) -> Value {
+    json!({ "notify": { "method": "session/update", "params": {
+        "sessionId": SESSION,
+        "update": { "sessionUpdate": "agent_message_chunk", "content": { "type": "text", "text": text } }
+    } } })
+}
+
+fn text(text: &str) -> Vec<ContentBlock> {
+    vec![ContentBlock::text(text)]
+}
+
+fn no_policies() -> HashMap<String, PermissionPolicy> {
+    HashMap::new()
+}
+
+#[tokio::test]
+async fn initialize_returns_agent_capabilities() {
+    let dir = workspace(handshake());
+    let client = spawn(&dir);
+    let result = client.initialize().await.unwrap();
+    assert!(result.agent_capabilities.load_session);
+    assert!(result.agent_capabilities.prompt_capabilities.image);
+    assert_eq!(result.agent_info.unwrap().name, "Mock Agent");
+    // Kept for deciding which content blocks prompts may use
+    assert!(client.agent_capabilities().prompt_capabilities.image);
+}
+
+#[tokio::test]
+async fn spawn_command_applies_args_env_and_working_dir() {
+    // The script is only found through the environment, relative to the working dir
+    let launch_dir = tempfile::tempdir().unwrap();
+    std::fs::write(launch_dir.path().join("custom-script.json"), Value::Array(handshake()).to_string()).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let command = AgentCommand {
+        program: env!("CARGO_BIN_EXE_mock-acp-agent").to_string(),
+        args: vec!["acp".to_string(), "--agent".to_string(), "notebook".to_string()],
+        env: HashMap::from([("MOCK_ACP_SCRIPT".to_string(), "custom-script.json".to_string())]),
+        working_dir: Some(launch_dir.path().display().to_string()),
+    };
+    let mut client = AcpClient::spawn_command(&command, dir.path().to_str().unwrap()).unwrap();
+    client.initialize().await.unwrap();
+    assert_eq!(client.new_session(dir.path().to_str().unwrap()).await.unwrap(), SESSION);
+    // The trace still belongs to the workspace
+    trace_of(&dir, &client);
+}
+
+#[tokio::test]
+async fn new_session_returns_session_id() {
+    let dir = workspace(handshake());
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    let session_id = client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    assert_eq!(session_id, SESSION);
+    assert_eq!(client.session_id.as_deref(), Some(SESSION));
+}
+
+#[tokio::test]
+async fn new_session_exposes_models_and_modes() {
+    let dir = workspace(vec![
+        json!({ "expect": "initialize" }),
+        json!({ "respond": { "protocolVersion": 1 } }),
+        json!({ "expect": "session/new" }),
+        json!({ "respond": {
+            "sessionId": SESSION,
+            "modes": { "currentModeId": "default", "availableModes": [
+                { "id": "default", "name": "Default" },
+                { "id": "planner", "name": "Planner", "description": "Plans before acting" }
+            ] },
+            "models": { "currentModelId": "auto", "availableModels": [
+                { "modelId": "auto", "name": "Auto" },
+                { "modelId": "claude-sonnet-4", "name": "Sonnet 4", "description": "1.30x credits" }
+            ] }
+        } }),
+        json!({ "expect": "session/set_mode" }),
+        json!({ "respond": {} }),
+        json!({ "expect": "session/set_model" }),
+        json!({ "respond": null }),
+    ]);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let capabilities = client.capabilities();
+    let modes = capabilities.modes.unwrap();
+    assert_eq!(modes.current_mode_id, "default");
+    assert_eq!(modes.available_modes[1].description.as_deref(), Some("Plans before acting"));
+    let models = capabilities.models.unwrap();
+    assert_eq!(models.available_models.len(), 2);
+
+    client.set_mode("planner").await.unwrap();
+    client.set_model("claude-sonnet-4").await.unwrap();
+    let capabilities = client.capabilities();
+    assert_eq!(capabilities.modes.unwrap().current_mode_id, "planner");
+    assert_eq!(capabilities.models.unwrap().current_model_id, "claude-sonnet-4");
+}
+
+#[tokio::test]
+async fn load_session_replays_and_succeeds() {
+    let dir = workspace(vec![
+        json!({ "expect": "initialize" }),
+        json!({ "respond": { "protocolVersion": 1 } }),
+        json!({ "expect": "session/load" }),
+        chunk("replayed history"),
+        json!({ "respond": { "sessionId": SESSION } }),
+    ]);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.load_session(SESSION, dir.path().to_str().unwrap()).await.unwrap();
+    assert_eq!(client.session_id.as_deref(), Some(SESSION));
+}
+
+#[tokio::test]
+async fn load_session_locked_by_another_process() {
+    let dir = workspace(vec![
+        json!({ "expect": "initialize" }),
+        json!({ "respond": { "protocolVersion": 1 } }),
+        json!({ "expect": "session/load" }),
+        json!({ "error": { "code": -32603, "message": "Session is active in another process (PID 4242)" } }),
+    ]);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    let err = client.load_session(SESSION, dir.path().to_str().unwrap()).await.unwrap_err();
+    assert!(matches!(&err, AppError::ProtocolError { code: -32603, message, .. } if message.contains("active in another process")), "{}", err);
+    assert!(client.session_id.is_none());
+}
+
+#[tokio::test]
+async fn load_session_unknown_to_the_agent() {
+    let dir = workspace(vec![
+        json!({ "expect": "initialize" }),
+        json!({ "respond": { "protocolVersion": 1 } }),
+        json!({ "expect": "session/load" }),
+        json!({ "error": { "code": -32002, "message": "Resource not found" } }),
+    ]);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    let err = client.load_session(SESSION, dir.path().to_str().unwrap()).await.unwrap_err();
+    assert_eq!(err, AppError::SessionNotFound { session_id: SESSION.to_string() });
+}
+
+#[tokio::test]
+async fn prompt_streams_chunks_until_response() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("Hello"),
+        chunk(", world"),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap();
+    assert_eq!(outcome.text, "Hello, world");
+    assert_eq!(outcome.stop_reason, Some(StopReason::EndTurn));
+    assert_eq!(outcome.chunk_count, 2);
+    assert_eq!(outcome.tool_call_count, 0);
+
+    let chunks: Vec<Value> = events.named("acp-chunk").iter().map(|c| c["text"].clone()).collect();
+    assert_eq!(chunks, vec![json!("Hello"), json!(", world")]);
+    let done = events.named("acp-done");
+    assert_eq!(done.len(), 1);
+    assert_eq!(done[0]["stopReason"], "end_turn");
+}
+
+#[tokio::test]
+async fn prompt_reports_max_tokens() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("cut o"),
+        json!({ "respond": { "stopReason": "max_tokens" } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap();
+    assert_eq!(outcome.stop_reason, Some(StopReason::MaxTokens));
+    assert_eq!(events.named("acp-done")[0]["stopReason"], "max_tokens");
+}
+
+fn update(update: Value) -> Value {
+    json!({ "notify": { "method": "session/update", "params": { "sessionId": SESSION, "update": update } } })
+}
+
+#[tokio::test]
+async fn prompt_reports_tool_calls() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        update(json!({
+            "sessionUpdate": "tool_call", "toolCallId": "call-1", "title": "Editing notes.md", "kind": "edit",
+            "status": "pending", "locations": [{ "path": "/ws/notes.md", "line": 3 }], "rawInput": { "path": "notes.md" }
+        })),
+        update(json!({
+            "sessionUpdate": "tool_call_update", "toolCallId": "call-1", "status": "completed",
+            "content": [
+                { "type": "diff", "path": "/ws/notes.md", "oldText": "a", "newText": "b" },
+                { "type": "content", "content": { "type": "text", "text": "1 line changed" } }
+            ]
+        })),
+        chunk("done"),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("edit it"), SESSION, &no_policies(), &events).await.unwrap();
+    assert_eq!(outcome.tool_call_count, 1);
+
+    let calls = events.named("acp-tool-call");
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0]["toolCallId"], "call-1");
+    assert_eq!(calls[0]["title"], "Editing notes.md");
+    assert_eq!(calls[0]["kind"], "edit");
+    assert_eq!(calls[0]["locations"], json!([{ "path": "/ws/notes.md", "line": 3 }]));
+    assert_eq!(calls[0]["rawInput"], json!({ "path": "notes.md" }));
+
+    let updates = events.named("acp-tool-call-update");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0]["status"], "completed");
+    assert!(updates[0].get("title").is_none());
+    assert_eq!(updates[0]["content"], json!([
+        { "type": "diff", "path": "/ws/notes.md", "oldText": "a", "newText": "b" },
+        { "type": "text", "text": "1 line changed" }
+    ]));
+}
+
+#[tokio::test]
+async fn prompt_forwards_thoughts_plans_commands_and_modes() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        update(json!({ "sessionUpdate": "agent_thought_chunk", "content": { "type": "text", "text": "Let me think" } })),
+        update(json!({ "sessionUpdate": "plan", "entries": [
+            { "content": "Read the PDF", "priority": "high", "status": "completed" },
+            { "content": "Summarise", "priority": "medium", "status": "in_progress" }
+        ] })),
+        update(json!({ "sessionUpdate": "available_commands_update", "availableCommands": [
+            { "name": "compact", "description": "Compact the context", "input": { "hint": "instructions" } }
+        ] })),
+        update(json!({ "sessionUpdate": "current_mode_update", "currentModeId": "planner" })),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("plan it"), SESSION, &no_policies(), &events).await.unwrap();
+    // Thoughts are not part of the answer
+    assert_eq!(outcome.text, "");
+
+    assert_eq!(events.named("acp-thought")[0]["text"], "Let me think");
+    let plan = &events.named("acp-plan")[0];
+    assert_eq!(plan["entries"][1], json!({ "content": "Summarise", "priority": "medium", "status": "in_progress" }));
+    let commands = &events.named("acp-commands")[0];
+    assert_eq!(commands["commands"][0], json!({ "name": "compact", "description": "Compact the context", "inputHint": "instructions" }));
+    assert_eq!(events.named("acp-mode")[0]["modeId"], "planner");
+}
+
+#[tokio::test]
+async fn prompt_outcome_keeps_usage_and_meta() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("ok"),
+        json!({ "respond": {
+            "stopReason": "end_turn",
+            "usage": { "inputTokens": 120, "outputTokens": 8 },
+            "_meta": { "credits": 0.25 }
+        } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap();
+    assert_eq!(outcome.usage, Some(json!({ "inputTokens": 120, "outputTokens": 8 })));
+    assert_eq!(outcome.meta, Some(json!({ "credits": 0.25 })));
+}
+
+#[tokio::test]
+async fn cancel_ends_the_turn() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("partial"),
+        json!({ "expect": "session/cancel" }),
+        json!({ "respond": { "stopReason": "cancelled" }, "to": "session/prompt" }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    let client = Arc::new(client);
+
+    let events = Arc::new(Recorder::default());
+    let prompt = {
+        let client = client.clone();
+        let events = events.clone();
+        tokio::spawn(async move {
+            client.prompt_streaming(text("long task"), SESSION, &no_policies(), events.as_ref()).await
+        })
+    };
+    while events.named("acp-chunk").is_empty() {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(client.cancel(SESSION).await.unwrap());
+
+    let outcome = prompt.await.unwrap().unwrap();
+    assert_eq!(outcome.text, "partial");
+    assert_eq!(outcome.stop_reason, Some(StopReason::Cancelled));
+    assert_eq!(events.named("acp-done")[0]["stopReason"], "cancelled");
+}
+
+#[tokio::test]
+async fn cancel_without_prompt_is_a_no_op() {
+    let dir = workspace(handshake());
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    assert!(!client.cancel(SESSION).await.unwrap());
+}
+
+#[tokio::test]
+async fn process_exit_mid_turn_keeps_partial_text() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("half an ans"),
+        json!({ "exit": 1 }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap();
+    assert_eq!(outcome.text, "half an ans");
+    assert_eq!(outcome.stop_reason, None);
+    assert!(events.named("acp-done").is_empty());
+}
+
+#[tokio::test]
+async fn process_exit_before_any_output_is_an_error() {
+    let mut steps = handshake();
+    steps.extend([json!({ "expect": "session/prompt" }), json!({ "exit": 1 })]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let err = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap_err();
+    assert_eq!(err, AppError::ProcessClosed);
+
+    // Later requests fail fast instead of hanging
+    assert!(client.set_model("auto").await.is_err());
+}
+
+#[tokio::test]
+async fn permission_request_follows_policy() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        json!({ "request": { "id": 7, "method": "session/request_permission", "params": {
+            "sessionId": SESSION,
+            "toolCall": { "toolCallId": "call-1", "title": "Run ls", "kind": "execute" },
+            "options": [
+                { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+                { "optionId": "deny", "name": "Deny", "kind": "reject_once" }
+            ]
+        } } }),
+        json!({ "echo_response": 7, "session": SESSION }),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let policies = HashMap::from([("execute".to_string(), PermissionPolicy::AutoDeny)]);
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("list files"), SESSION, &policies, &events).await.unwrap();
+    let reply: Value = serde_json::from_str(&outcome.text).unwrap();
+    assert_eq!(reply.pointer("/result/outcome/outcome"), Some(&json!("selected")));
+    assert_eq!(reply.pointer("/result/outcome/optionId"), Some(&json!("deny")));
+    assert!(events.named("acp-permission").is_empty());
+}
+
+#[tokio::test]
+async fn fs_read_text_file_is_served_by_the_client() {
+    let dir = tempfile::tempdir().unwrap();
+    let notes = dir.path().join("notes.txt");
+    std::fs::write(&notes, "one\ntwo\nthree\nfour").unwrap();
+
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        json!({ "request": { "id": 9, "method": "fs/read_text_file", "params": {
+            "sessionId": SESSION, "path": notes, "line": 2, "limit": 2
+        } } }),
+        json!({ "echo_response": 9, "session": SESSION }),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    write_script(&dir, steps);
+
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("read"), SESSION, &no_policies(), &events).await.unwrap();
+    let reply: Value = serde_json::from_str(&outcome.text).unwrap();
+    assert_eq!(reply.pointer("/result/content"), Some(&json!("two\nthree")));
+}
+
+#[tokio::test]
+async fn fs_read_text_file_outside_the_workspace_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let secret = outside.path().join("secret.txt");
+    std::fs::write(&secret, "hidden").unwrap();
+
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        json!({ "request": { "id": 9, "method": "fs/read_text_file", "params": {
+            "sessionId": SESSION, "path": secret
+        } } }),
+        json!({ "echo_response": 9, "session": SESSION }),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    write_script(&dir, steps);
+
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("read"), SESSION, &no_policies(), &events).await.unwrap();
+    let reply: Value = serde_json::from_str(&outcome.text).unwrap();
+    assert_eq!(reply.pointer("/result"), None);
+    assert_eq!(reply.pointer("/error/data/kind"), Some(&json!("accessDenied")));
+}
+
+#[tokio::test]
+async fn crash_is_detected_with_stderr() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "stderr": "panicked at 'model backend unreachable'" }),
+        json!({ "exit": 101 }),
+    ]);
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    assert!(client.wait_exit().await);
+    assert!(client.is_closed());
+    // stdout can close before the process is reaped and stderr is drained
+    for _ in 0..100 {
+        if client.exit_status().is_some() && !client.stderr_tail().is_empty() { break; }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(client.exit_status().unwrap().contains("101"));
+    assert_eq!(client.stderr_tail(), vec!["panicked at 'model backend unreachable'".to_string()]);
+}
+
+#[tokio::test]
+async fn kill_is_not_a_crash() {
+    let dir = workspace(handshake());
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    client.kill();
+    assert!(!client.wait_exit().await);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn killed_agent_is_reaped() {
+    let dir = workspace(handshake());
+    let client = spawn(&dir);
+    client.initialize().await.unwrap();
+    let pid = client.diagnostics().pid.unwrap() as i32;
+    client.kill();
+    // A zombie still answers signal 0; a reaped process does not
+    for _ in 0..200 {
+        if unsafe { libc::kill(pid, 0) } != 0 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("agent process {} was not reaped", pid);
+}
+
+/// Where `client` traced its messages, checked to be under the workspace's logs.
+fn trace_of(dir: &tempfile::TempDir, client: &AcpClient) -> std::path::PathBuf {
+    let path = std::path::PathBuf::from(client.diagnostics().trace_path.expect("no trace"));
+    assert!(path.starts_with(dir.path().join(trace::LOGS_DIR)), "{}", path.display());
+    path
+}
+
+#[tokio::test]
+async fn diagnostics_report_process_and_trace() {
+    let mut steps = handshake();
+    steps.push(json!({ "stderr": "starting up" }));
+    let dir = workspace(steps);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    for _ in 0..100 {
+        if !client.stderr_tail().is_empty() { break; }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+
+    let diagnostics = client.diagnostics();
+    assert!(diagnostics.running);
+    assert!(diagnostics.pid.is_some());
+    assert_eq!(diagnostics.exit_status, None);
+    assert_eq!(diagnostics.stderr, vec!["starting up".to_string()]);
+    assert_eq!(diagnostics.agent_info.unwrap().name, "Mock Agent");
+
+    let entries = trace::read(&trace_of(&dir, &client)).unwrap();
+    let methods: Vec<(Direction, Option<&str>)> = entries.iter()
+        .map(|e| (e.direction, e.message.get("method").and_then(|m| m.as_str())))
+        .collect();
+    assert_eq!(methods, vec![
+        (Direction::Out, Some("initialize")),
+        (Direction::In, None),
+        (Direction::Out, Some("session/new")),
+        (Direction::In, None),
+    ]);
+}
+
+#[tokio::test]
+async fn recorded_trace_replays_the_same_turn() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("Hello"),
+        json!({ "request": { "id": 9, "method": "session/request_permission", "params": {
+            "sessionId": SESSION,
+            "toolCall": { "toolCallId": "t1", "title": "Edit notes.md", "kind": "edit" },
+            "options": [{ "optionId": "yes", "name": "Allow", "kind": "allow_once" }]
+        } } }),
+        chunk(", again"),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let policies = HashMap::from([("edit".to_string(), PermissionPolicy::AutoAllow)]);
+    let mut client = spawn(&dir);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    let recorded = client.prompt_streaming(text("hi"), SESSION, &policies, &Recorder::default()).await.unwrap();
+    let trace = trace_of(&dir, &client);
+    client.kill();
+
+    write_script(&dir, vec![json!({ "replay_trace": trace })]);
+    let mut replay = spawn(&dir);
+    replay.initialize().await.unwrap();
+    replay.new_session(dir.path().to_str().unwrap()).await.unwrap();
+    let replayed = replay.prompt_streaming(text("hi"), SESSION, &policies, &Recorder::default()).await.unwrap();
+    assert_eq!(replayed.text, "Hello, again");
+    assert_eq!(replayed.text, recorded.text);
+    assert_eq!(replayed.stop_reason, recorded.stop_reason);
+}
+
+#[tokio::test]
+async fn unanswered_control_request_times_out() {
+    // The agent reads session/new and never answers
+    let dir = workspace(handshake()[..3].to_vec());
+    let timeouts = Timeouts { new_session_secs: 1, ..Timeouts::default() };
+    let mut client = spawn(&dir).with_timeouts(timeouts);
+    client.initialize().await.unwrap();
+    let err = client.new_session(dir.path().to_str().unwrap()).await.unwrap_err();
+    assert_eq!(err, AppError::Timeout { method: "session/new".to_string(), seconds: 1 });
+    assert!(client.session_id.is_none());
+}
+
+#[tokio::test]
+async fn silent_prompt_is_reported_as_stalled() {
+    let mut steps = handshake();
+    steps.extend([
+        json!({ "expect": "session/prompt" }),
+        chunk("Thinking"),
+        json!({ "sleep_ms": 1500 }),
+        chunk(" done"),
+        json!({ "respond": { "stopReason": "end_turn" } }),
+    ]);
+    let dir = workspace(steps);
+    let timeouts = Timeouts { prompt_inactivity_secs: 1, ..Timeouts::default() };
+    let mut client = spawn(&dir).with_timeouts(timeouts);
+    client.initialize().await.unwrap();
+    client.new_session(dir.path().to_str().unwrap()).await.unwrap();
+
+    let events = Recorder::default();
+    let outcome = client.prompt_streaming(text("hi"), SESSION, &no_policies(), &events).await.unwrap();
+    // A stall is a warning; the turn still completes
+    assert_eq!(outcome.text, "Thinking done");
+    let stalled = events.named("acp-stalled");
+    assert_eq!(stalled.len(), 1);
+    assert_eq!(stalled[0]["sessionId"], SESSION);
+    assert_eq!(stalled[0]["idleSeconds"], 1);
+}
@@ -0,0 +1,167 @@
+//! Stand-in for `kiro-cli acp` that replays a JSON script instead of talking to a model.
+//!
+//! The script is read from `$MOCK_ACP_SCRIPT`, or `mock-acp-script.json` in the working
+//! directory. It is a JSON array of steps, run in order:
+//!
+//! - `{"expect": "session/new"}` — read the next message and fail unless it has this method
+//! - `{"respond": {...}}` — answer the last request read; `"to": "<method>"` picks the
+//!   last request with that method instead
+//! - `{"error": {"code": -32000, "message": "..."}}` — same, with an error response
+//! - `{"notify": {"method": "...", "params": {...}}}` — send a notification
+//! - `{"request": {"id": 7, "method": "...", "params": {...}}}` — send a request to the client
+//! - `{"echo_response": 7, "session": "<id>"}` — wait for the client's answer to request 7
+//!   and stream it back as an `agent_message_chunk`, so tests can assert on it
+//! - `{"sleep_ms": 50}`, `{"stderr": "..."}`, `{"exit": 1}`
+//! - `{"replay_trace": "<path>"}` — play the agent's side of a trace recorded by the app
+//!   (`.kiro-notebook/logs/*.jsonl`): messages the client sent are expected by method,
+//!   the agent's are sent back with response ids matched to the live requests
+//!
+//! Once the script is done the agent idles until stdin closes.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+fn send(out: &mut impl Write, msg: &Value) {
+    let _ = writeln!(out, "{}", msg);
+    let _ = out.flush();
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("[mock-acp-agent] {}", msg);
+    std::process::exit(3);
+}
+
+fn main() {
+    let path = std::env::var("MOCK_ACP_SCRIPT").unwrap_or_else(|_| "mock-acp-script.json".to_string());
+    let script = std::fs::read_to_string(&path).unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e)));
+    let steps: Vec<Value> = serde_json::from_str(&script).unwrap_or_else(|e| fail(&format!("bad script: {}", e)));
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut out = io::stdout();
+
+    // Last request id seen, overall and per method
+    let mut last_request: Option<Value> = None;
+    let mut requests_by_method: HashMap<String, Value> = HashMap::new();
+    // Client responses that arrived while waiting for something else
+    let mut responses: HashMap<String, Value> = HashMap::new();
+
+    // Reads the next request or notification; client responses are stashed on the way.
+    // `None` when stdin closes, or when `stop_at` (a response id) arrives.
+    let mut read_message = |responses: &mut HashMap<String, Value>, stop_at: Option<&str>| -> Option<Value> {
+        loop {
+            let line = lines.next()?.ok()?;
+            if line.trim().is_empty() { continue; }
+            let msg: Value = serde_json::from_str(&line).unwrap_or_else(|e| fail(&format!("bad json from client: {}", e)));
+            if msg.get("method").is_none() {
+                let id = msg.get("id").cloned().unwrap_or(Value::Null).to_string();
+                let done = stop_at == Some(id.as_str());
+                responses.insert(id, msg);
+                if done { return None; }
+                continue;
+            }
+            return Some(msg);
+        }
+    };
+
+    for step in &steps {
+        if let Some(method) = step.get("expect").and_then(|v| v.as_str()) {
+            let msg = read_message(&mut responses, None).unwrap_or_else(|| fail(&format!("stdin closed, expected {}", method)));
+            let got = msg.get("method").and_then(|v| v.as_str()).unwrap_or("");
+            if got != method {
+                fail(&format!("expected {}, got {}", method, got));
+            }
+            if let Some(id) = msg.get("id") {
+                last_request = Some(id.clone());
+                requests_by_method.insert(method.to_string(), id.clone());
+            }
+        } else if step.get("respond").is_some() || step.get("error").is_some() {
+            let id = match step.get("to").and_then(|v| v.as_str()) {
+                Some(method) => requests_by_method.get(method).cloned(),
+                None => last_request.clone(),
+            }
+            .unwrap_or_else(|| fail("nothing to respond to"));
+            let msg = match step.get("error") {
+                Some(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                None => json!({ "jsonrpc": "2.0", "id": id, "result": step["respond"] }),
+            };
+            send(&mut out, &msg);
+        } else if let Some(notification) = step.get("notify") {
+            let mut msg = notification.clone();
+            msg["jsonrpc"] = json!("2.0");
+            send(&mut out, &msg);
+        } else if let Some(request) = step.get("request") {
+            let mut msg = request.clone();
+            msg["jsonrpc"] = json!("2.0");
+            send(&mut out, &msg);
+        } else if let Some(id) = step.get("echo_response") {
+            let key = id.to_string();
+            while !responses.contains_key(&key) {
+                if read_message(&mut responses, Some(&key)).is_none() && !responses.contains_key(&key) {
+                    fail(&format!("stdin closed, expected response {}", key));
+                }
+            }
+            let response = responses.remove(&key).unwrap();
+            send(&mut out, &json!({
+                "jsonrpc": "2.0",
+                "method": "session/update",
+                "params": {
+                    "sessionId": step.get("session").cloned().unwrap_or(Value::Null),
+                    "update": {
+                        "sessionUpdate": "agent_message_chunk",
+                        "content": { "type": "text", "text": response.to_string() }
+                    }
+                }
+            }));
+        } else if let Some(path) = step.get("replay_trace").and_then(|v| v.as_str()) {
+            let trace = std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e)));
+            // Request ids in the trace -> ids of the same requests in this run
+            let mut ids: HashMap<String, Value> = HashMap::new();
+            for line in trace.lines().filter(|l| !l.trim().is_empty()) {
+                let entry: Value = serde_json::from_str(line).unwrap_or_else(|e| fail(&format!("bad trace line: {}", e)));
+                let msg = &entry["message"];
+                match (entry["direction"].as_str(), msg.get("method")) {
+                    (Some("out"), Some(method)) => {
+                        let got = read_message(&mut responses, None)
+                            .unwrap_or_else(|| fail(&format!("stdin closed, expected {}", method)));
+                        if got.get("method") != Some(method) {
+                            fail(&format!("expected {}, got {}", method, got.get("method").unwrap_or(&Value::Null)));
+                        }
+                        if let (Some(traced), Some(live)) = (msg.get("id"), got.get("id")) {
+                            ids.insert(traced.to_string(), live.clone());
+                        }
+                    }
+                    // The client's answer to one of our requests; wait for it to keep the order
+                    (Some("out"), None) => {
+                        let key = msg.get("id").cloned().unwrap_or(Value::Null).to_string();
+                        while !responses.contains_key(&key) {
+                            if read_message(&mut responses, Some(&key)).is_none() && !responses.contains_key(&key) {
+                                fail(&format!("stdin closed, expected response {}", key));
+                            }
+                        }
+                        responses.remove(&key);
+                    }
+                    (Some("in"), Some(_)) => send(&mut out, msg),
+                    (Some("in"), None) => {
+                        let mut msg = msg.clone();
+                        let traced = msg.get("id").cloned().unwrap_or(Value::Null).to_string();
+                        msg["id"] = ids.get(&traced).cloned().unwrap_or_else(|| fail(&format!("response {} to unknown request", traced)));
+                        send(&mut out, &msg);
+                    }
+                    _ => fail(&format!("bad trace entry: {}", entry)),
+                }
+            }
+        } else if let Some(ms) = step.get("sleep_ms").and_then(|v| v.as_u64()) {
+            std::thread::sleep(std::time::Duration::from_millis(ms));
+        } else if let Some(text) = step.get("stderr").and_then(|v| v.as_str()) {
+            eprintln!("{}", text);
+        } else if let Some(code) = step.get("exit").and_then(|v| v.as_i64()) {
+            std::process::exit(code as i32);
+        } else {
+            fail(&format!("unknown step: {}", step));
+        }
+    }
+
+    while read_message(&mut responses, None).is_some() {}
+}
@@ -6,9 +6,9 @@ import { open } from "@tauri-apps/plugin-dialog";
 import ReactMarkdown from "react-markdown";
 import remarkGfm from "remark-gfm";
 import {
-  FileText, FileType, FileCode, Globe, Paperclip, FolderOpen,
+  FileText, FileType, FileCode, Globe, BookOpen, Paperclip, FolderOpen,
   RefreshCw, Plus, ClipboardList, X, ChevronRight, ChevronDown,
-  ArrowUp, Square, Wrench, MessageSquare, Check,
+  ArrowUp, Square, Wrench, MessageSquare, Check, Image as ImageIcon, Settings as SettingsIcon,
 } from "lucide-react";
 import "./App.css";
 
@@ -24,14 +24,220 @@ interface ChatMessage {
   content: string;
 }
 
+interface PermissionOption {
+  optionId: string;
+  name: string;
+  kind: string;
+}
+
+interface PermissionRequest {
+  sessionId: string;
+  requestId: string | number;
+  kind: string;
+  toolCall: { title?: string };
+  options: PermissionOption[];
+}
+
+interface ToolCallEvent {
+  sessionId: string;
+  toolCallId: string;
+  title?: string;
+  kind?: string;
+  status?: "pending" | "in_progress" | "completed" | "failed";
+}
+
+interface PromptOutcome {
+  text: string;
+  stopReason: "end_turn" | "max_tokens" | "max_turn_requests" | "refusal" | "cancelled" | null;
+  usage: Record<string, unknown> | null;
+  meta: Record<string, unknown> | null;
+  durationMs: number;
+  toolCallCount: number;
+  chunkCount: number;
+}
+
+interface SessionCapabilities {
+  models: { currentModelId: string; availableModels: { modelId: string; name: string; description?: string }[] } | null;
+  modes: { currentModeId: string; availableModes: { id: string; name: string; description?: string }[] } | null;
+}
+
+interface CrashedEvent {
+  sessionId: string;
+  exitStatus: string | null;
+  stderr: string[];
+  tracePath: string | null;
+  interruptedPrompt: boolean;
+}
+
+interface KiroCliSettings {
+  path: string | null;
+  args: string[];
+  env: Record<string, string>;
+  workingDir: string | null;
+}
+
+interface AgentProfile {
+  id: string;
+  name: string;
+  command: string;
+  args: string[];
+  env: Record<string, string>;
+  workingDir: string | null;
+  quirks: Record<string, unknown>;
+}
+
+interface AppSettings {
+  kiroCli: KiroCliSettings;
+  agents: AgentProfile[];
+  defaultAgent: string | null;
+  poolSize: number;
+  maxLiveProcesses: number;
+  timeouts: Record<TimeoutKey, number>;
+}
+
+type TimeoutKey = "initializeSecs" | "newSessionSecs" | "loadSessionSecs" | "setModelSecs" | "promptInactivitySecs";
+
+const TIMEOUT_LABELS: Record<TimeoutKey, string> = {
+  initializeSecs: "Start-up (initialize)",
+  newSessionSecs: "New session",
+  loadSessionSecs: "Load session",
+  setModelSecs: "Switch model or mode",
+  promptInactivitySecs: "Warn when a reply stalls for",
+};
+
+interface CliCheck {
+  path: string;
+  args: string[];
+  protocolVersion: number;
+  agentInfo: { name: string; version: string; title?: string } | null;
+  agentCapabilities: Record<string, unknown>;
+}
+
+interface RestartEvent {
+  sessionId: string;
+  interruptedPrompt: boolean;
+  error?: AppError;
+}
+
+// Commands reject with this instead of a plain string; see error.rs
+type AppError =
+  | { kind: "cliNotFound"; path: string | null }
+  | { kind: "spawnFailed"; program: string; message: string }
+  | { kind: "cliCheckFailed"; cause: AppError; stderr: string[] }
+  | { kind: "agentNotFound"; agentId: string }
+  | { kind: "protocolError"; code: number; message: string; data: unknown }
+  | { kind: "invalidResponse"; method: string; message: string }
+  | { kind: "sessionLocked"; sessionId: string; pid: number | null; startedAt: string | null }
+  | { kind: "sessionNotFound"; sessionId: string }
+  | { kind: "invalidSessionId"; sessionId: string }
+  | { kind: "sessionSuspended"; sessionId: string }
+  | { kind: "noActiveSession" }
+  | { kind: "promptRunning"; sessionId: string }
+  | { kind: "noInterruptedPrompt"; sessionId: string }
+  | { kind: "permissionNotPending" }
+  | { kind: "timeout"; method: string; seconds: number }
+  | { kind: "processClosed" }
+  | { kind: "noWorkspace" }
+  | { kind: "noUserDirectory"; name: string }
+  | { kind: "fileNotFound"; path: string }
+  | { kind: "accessDenied"; path: string }
+  | { kind: "outsideNotes"; path: string; notes: string }
+  | { kind: "unsupportedFileType"; extension: string }
+  | { kind: "pageOutOfRange"; from: number; to: number; pages: number }
+  | { kind: "invalidArgument"; name: string; message: string }
+  | { kind: "unknownTool"; name: string }
+  | { kind: "extractionFailed"; path: string; message: string }
+  | { kind: "io"; path: string; message: string }
+  | { kind: "internal"; message: string };
+
+function isError<K extends AppError["kind"]>(e: unknown, kind: K): e is Extract<AppError, { kind: K }> {
+  return typeof e === "object" && e !== null && (e as AppError).kind === kind;
+}
+
+function isTimeout(e: unknown): e is Extract<AppError, { kind: "timeout" }> {
+  return isError(e, "timeout");
+}
+
+function errorText(e: unknown): string {
+  if (typeof e !== "object" || e === null || !("kind" in e)) return String(e);
+  const error = e as AppError;
+  switch (error.kind) {
+    case "cliNotFound":
+      return error.path ? `kiro-cli not found at ${error.path}` : "kiro-cli not found. Install Kiro CLI or set its path in the settings";
+    case "spawnFailed":
+      return `Could not start ${error.program}: ${error.message}`;
+    case "cliCheckFailed":
+      return [errorText(error.cause), ...error.stderr].join("\n");
+    case "agentNotFound":
+      return `No agent "${error.agentId}" in the settings`;
+    case "protocolError":
+      return `The agent reported an error: ${error.message} (code ${error.code})`;
+    case "invalidResponse":
+      return `The agent sent an unexpected ${error.method} response: ${error.message}`;
+    case "sessionLocked":
+      return `This session is open in another process${error.pid ? ` (PID ${error.pid})` : ""}`;
+    case "sessionNotFound":
+      return "The agent no longer knows this session";
+    case "invalidSessionId":
+      return `"${error.sessionId}" is not a valid session id`;
+    case "sessionSuspended":
+      return "This session's agent is suspended; it starts again with the next prompt";
+    case "noActiveSession":
+      return "The agent has no session yet";
+    case "promptRunning":
+      return "A prompt is already running in this session";
+    case "noInterruptedPrompt":
+      return "There is no interrupted prompt to send again";
+    case "permissionNotPending":
+      return "That permission request has already been answered";
+    case "timeout":
+      return `${error.method} got no response within ${error.seconds}s`;
+    case "processClosed":
+      return "The agent process exited";
+    case "noWorkspace":
+      return "Open a workspace first";
+    case "noUserDirectory":
+      return `Cannot find your ${error.name} directory`;
+    case "fileNotFound":
+      return `${error.path} does not exist`;
+    case "accessDenied":
+      return `${error.path} is outside the workspace`;
+    case "outsideNotes":
+      return `Notes can only be written under ${error.notes}`;
+    case "unsupportedFileType":
+      return `.${error.extension} files cannot be read`;
+    case "pageOutOfRange":
+      return error.pages ? `Pages ${error.from}-${error.to} are out of range; the file has ${error.pages} pages` : "This file has no pages";
+    case "invalidArgument":
+      return `Invalid ${error.name}: ${error.message}`;
+    case "unknownTool":
+      return `Unknown tool: ${error.name}`;
+    case "extractionFailed":
+      return `Could not read ${error.path}: ${error.message}`;
+    case "io":
+      return `${error.path}: ${error.message}`;
+    case "internal":
+      return `Internal error: ${error.message}`;
+  }
+}
+
+const STOP_NOTES: Record<string, string> = {
+  cancelled: "Stopped",
+  max_tokens: "Stopped: reached the token limit",
+  max_turn_requests: "Stopped: reached the request limit",
+  refusal: "The agent declined to answer",
+};
+
 interface ChatSession {
   id: string;
   label: string;
   messages: ChatMessage[];
   contextFiles?: string[];
   sentContextFiles?: string[];
+  agentId?: string;
 }
 
+// Used when the agent does not report its models
 const MODELS = [
   { id: "auto", label: "Auto", credits: "1.00x" },
   { id: "claude-sonnet-4.6", label: "Sonnet 4.6", credits: "1.30x" },
@@ -42,12 +248,31 @@ const MODELS = [
   { id: "claude-haiku-4.5", label: "Haiku 4.5", credits: "0.40x" },
 ];
 
+const IMAGE_EXTENSIONS = ["png", "jpg", "jpeg"];
+const HTML_EXTENSIONS = ["html", "htm", "xhtml"];
+
+function isImage(path: string): boolean {
+  return IMAGE_EXTENSIONS.includes(path.split(".").pop()?.toLowerCase() || "");
+}
+
+type PromptPart =
+  | { type: "text"; text: string }
+  | { type: "image"; path: string }
+  | { type: "resource"; path: string }
+  | { type: "resource_link"; path: string };
+
 const FILE_ICONS: Record<string, React.ReactNode> = {
   pdf: <FileText size={14} className="icon-pdf" />,
   docx: <FileType size={14} className="icon-docx" />,
   md: <FileCode size={14} className="icon-md" />,
   txt: <FileText size={14} className="icon-txt" />,
   html: <Globe size={14} className="icon-html" />,
+  htm: <Globe size={14} className="icon-html" />,
+  xhtml: <Globe size={14} className="icon-html" />,
+  epub: <BookOpen size={14} className="icon-epub" />,
+  png: <ImageIcon size={14} />,
+  jpg: <ImageIcon size={14} />,
+  jpeg: <ImageIcon size={14} />,
 };
 
 function getFileIcon(ext: string): React.ReactNode {
@@ -63,20 +288,34 @@ export default function App() {
   const [messages, setMessages] = useState<ChatMessage[]>([]);
   const [sessions, setSessions] = useState<ChatSession[]>([]);
   const [currentSessionId, setCurrentSessionId] = useState<string | null>(null);
+  // A history entry shown without an agent because another process holds its session
+  const [readOnlySession, setReadOnlySession] = useState<ChatSession | null>(null);
   const [input, setInput] = useState("");
   const [loading, setLoading] = useState(false);
   const [statusText, setStatusText] = useState("");
+  const [stalled, setStalled] = useState<{ sessionId: string; idleSeconds: number } | null>(null);
   const [contextCollapsed, setContextCollapsed] = useState(false);
   const [previewWidth, setPreviewWidth] = useState(50);
   const [showHistory, setShowHistory] = useState(false);
   const [historyList, setHistoryList] = useState<ChatSession[]>([]);
   const [selectedModel, setSelectedModel] = useState("auto");
+  const [capabilities, setCapabilities] = useState<SessionCapabilities | null>(null);
+  const [permissionRequests, setPermissionRequests] = useState<PermissionRequest[]>([]);
+  const [showSettings, setShowSettings] = useState(false);
+  const [appSettings, setAppSettings] = useState<AppSettings | null>(null);
+  const [cliSettings, setCliSettings] = useState<KiroCliSettings | null>(null);
+  const [agentsJson, setAgentsJson] = useState("");
+  const [agents, setAgents] = useState<AgentProfile[]>([]);
+  const [selectedAgent, setSelectedAgent] = useState<string | null>(null);
+  const [cliCheck, setCliCheck] = useState<string>("");
   const chatEndRef = useRef<HTMLDivElement>(null);
   const streamingRef = useRef("");
   const draggingRef = useRef(false);
   const panelsRef = useRef<HTMLDivElement>(null);
   const sentContextRef = useRef<Set<string>>(new Set());
   const currentSessionRef = useRef<string | null>(null);
+  // Sessions whose agent was restarted mid-prompt; asked about once their tab is open
+  const interruptedRef = useRef<Set<string>>(new Set());
 
   // Keep ref in sync
   useEffect(() => { currentSessionRef.current = currentSessionId; }, [currentSessionId]);
@@ -98,6 +337,7 @@ export default function App() {
         label: session?.label || currentSessionId,
         messages: JSON.stringify(messages),
         contextFiles: curSent,
+        agentId: session?.agentId ?? null,
       }).catch(() => {});
     }
   }, [messages, currentSessionId, contextFiles]);
@@ -135,14 +375,14 @@ export default function App() {
   const selectFile = useCallback(async (file: FileEntry) => {
     setSelectedFile(file);
     setContextFiles((prev) => new Set(prev).add(file.path));
-    if (file.extension === "pdf" || file.extension === "html") {
+    if (file.extension === "pdf" || HTML_EXTENSIONS.includes(file.extension) || IMAGE_EXTENSIONS.includes(file.extension)) {
       setFileContent("");
       return;
     }
     try {
       setFileContent(await invoke<string>("read_file_content", { path: file.path }));
     } catch (e) {
-      setFileContent(`Error reading file: ${e}`);
+      setFileContent(`Error reading file: ${errorText(e)}`);
     }
   }, []);
 
@@ -159,6 +399,26 @@ export default function App() {
     });
   }, []);
 
+  // Kill a wedged agent and reload its session in a new process
+  const restartAgent = useCallback(async (sessionId: string) => {
+    setStalled(null);
+    try {
+      await invoke("restart_session", { sessionId });
+      if (sessionId === currentSessionRef.current) {
+        setMessages((prev) => [...prev, { role: "assistant", content: "↻ The agent was restarted" }]);
+      }
+    } catch (e) {
+      setMessages((prev) => [...prev, { role: "assistant", content: `❌ Could not restart the agent: ${errorText(e)}` }]);
+    }
+  }, []);
+
+  // A control request that timed out leaves the agent in doubt; offer a restart
+  const offerRestart = useCallback((sessionId: string, e: unknown) => {
+    if (isTimeout(e) && window.confirm(`${errorText(e)}. Restart the agent?`)) {
+      restartAgent(sessionId);
+    }
+  }, [restartAgent]);
+
   const changeModel = useCallback(async (modelId: string) => {
     setSelectedModel(modelId);
     const sid = currentSessionRef.current;
@@ -167,19 +427,48 @@ export default function App() {
         await invoke("set_model", { sessionId: sid, modelId });
       } catch (e) {
         console.error("Failed to set model", e);
+        offerRestart(sid, e);
       }
     }
+  }, [offerRestart]);
+
+  const changeMode = useCallback(async (modeId: string) => {
+    const sid = currentSessionRef.current;
+    if (!sid) return;
+    try {
+      await invoke("set_mode", { sessionId: sid, modeId });
+      setCapabilities((prev) => prev?.modes ? { ...prev, modes: { ...prev.modes, currentModeId: modeId } } : prev);
+    } catch (e) {
+      console.error("Failed to set mode", e);
+      offerRestart(sid, e);
+    }
+  }, [offerRestart]);
+
+  const refreshCapabilities = useCallback(async (sessionId: string) => {
+    try {
+      const caps = await invoke<SessionCapabilities>("get_session_capabilities", { sessionId });
+      if (currentSessionRef.current === sessionId) setCapabilities(caps);
+    } catch {
+      setCapabilities(null);
+    }
   }, []);
 
+  useEffect(() => {
+    if (currentSessionId) refreshCapabilities(currentSessionId);
+    else setCapabilities(null);
+  }, [currentSessionId, refreshCapabilities]);
+
   const runPrompt = useCallback(
-    async (sessionId: string, message: string, ctx: string[]) => {
+    async (sessionId: string, message: string, ctx: string[], retry = false) => {
       streamingRef.current = "";
       setMessages((prev) => [...prev, { role: "assistant", content: "" }]);
       setLoading(true);
       setStatusText("");
+      setStalled(null);
 
       const unlisten1 = await listen<{ sessionId: string; text: string }>("acp-chunk", (e) => {
         if (e.payload.sessionId !== sessionId) return;
+        setStalled(null);
         streamingRef.current += e.payload.text;
         setMessages((prev) => {
           const updated = [...prev];
@@ -188,30 +477,136 @@ export default function App() {
         });
       });
 
-      const unlisten2 = await listen<{ sessionId: string; type: string; title?: string; status?: string }>("acp-status", (e) => {
+      const toolTitles = new Map<string, string>();
+      const unlistenToolCall = await listen<ToolCallEvent>("acp-tool-call", (e) => {
+        if (e.payload.sessionId !== sessionId) return;
+        toolTitles.set(e.payload.toolCallId, e.payload.title || "Working...");
+        setStalled(null);
+        setStatusText(e.payload.title || "Working...");
+      });
+      const unlistenToolUpdate = await listen<ToolCallEvent>("acp-tool-call-update", (e) => {
+        if (e.payload.sessionId !== sessionId) return;
+        if (e.payload.title) toolTitles.set(e.payload.toolCallId, e.payload.title);
+        setStalled(null);
+        if (e.payload.status === "completed" || e.payload.status === "failed") setStatusText("");
+        else setStatusText(toolTitles.get(e.payload.toolCallId) || "Working...");
+      });
+      const unlistenThought = await listen<{ sessionId: string; text: string }>("acp-thought", (e) => {
+        if (e.payload.sessionId !== sessionId) return;
+        setStalled(null);
+        setStatusText("Thinking...");
+      });
+      const unlistenStalled = await listen<{ sessionId: string; idleSeconds: number }>("acp-stalled", (e) => {
+        if (e.payload.sessionId !== sessionId) return;
+        setStalled(e.payload);
+      });
+
+      const unlistenMode = await listen<{ sessionId: string; modeId: string }>("acp-mode", (e) => {
+        if (e.payload.sessionId !== sessionId || currentSessionRef.current !== sessionId) return;
+        const modeId = e.payload.modeId;
+        setCapabilities((prev) => prev?.modes ? { ...prev, modes: { ...prev.modes, currentModeId: modeId } } : prev);
+      });
+
+      const unlisten3 = await listen<PermissionRequest>("acp-permission", (e) => {
         if (e.payload.sessionId !== sessionId) return;
-        if (e.payload.type === "tool_call") setStatusText(e.payload.title || "Working...");
-        else if (e.payload.type === "tool_update" && e.payload.status === "completed") setStatusText("");
+        setPermissionRequests((prev) => [...prev, e.payload]);
       });
 
       try {
-        await invoke<string>("send_prompt", { sessionId, message, contextFiles: ctx });
+        // Context documents go first, as embedded resources; images inline
+        const prompt: PromptPart[] = [
+          ...ctx.map((path): PromptPart => isImage(path) ? { type: "image", path } : { type: "resource", path }),
+          { type: "text", text: message },
+        ];
+        const outcome = retry
+          ? await invoke<PromptOutcome>("retry_prompt", { sessionId })
+          : await invoke<PromptOutcome>("send_prompt", { sessionId, prompt });
+        const note = outcome.stopReason ? STOP_NOTES[outcome.stopReason] : "Interrupted: the agent process exited";
+        if (note) {
+          setMessages((prev) => {
+            const updated = [...prev];
+            updated[updated.length - 1] = { role: "assistant", content: `${streamingRef.current}\n\n_${note}_` };
+            return updated;
+          });
+        }
       } catch (e) {
         setMessages((prev) => {
           const updated = [...prev];
-          updated[updated.length - 1] = { role: "assistant", content: streamingRef.current || `❌ Error: ${e}` };
+          updated[updated.length - 1] = { role: "assistant", content: streamingRef.current || `❌ Error: ${errorText(e)}` };
           return updated;
         });
       }
 
       unlisten1();
-      unlisten2();
+      unlistenToolCall();
+      unlistenToolUpdate();
+      unlistenThought();
+      unlistenStalled();
+      unlistenMode();
+      unlisten3();
+      setPermissionRequests((prev) => prev.filter((p) => p.sessionId !== sessionId));
       setLoading(false);
       setStatusText("");
+      setStalled(null);
     },
     [],
   );
 
+  // Only an explicit "no" drops the interrupted prompt
+  const offerRetry = useCallback(
+    (sessionId: string) => {
+      if (!interruptedRef.current.delete(sessionId)) return;
+      if (window.confirm("The agent was restarted. Send the interrupted prompt again?")) {
+        setMessages((prev) => [...prev, { role: "user", content: "↻ Retrying the interrupted prompt" }]);
+        runPrompt(sessionId, "", [], true);
+      } else {
+        invoke("discard_interrupted_prompt", { sessionId }).catch(() => {});
+      }
+    },
+    [runPrompt],
+  );
+
+  // The backend restarts crashed agents and reloads their session
+  useEffect(() => {
+    const pending: Array<Promise<() => void>> = [
+      listen<CrashedEvent>("acp-crashed", (e) => {
+        if (e.payload.sessionId !== currentSessionRef.current) return;
+        const stderr = e.payload.stderr.slice(-5).join("\n");
+        const trace = e.payload.tracePath ? `\n\nProtocol trace: \`${e.payload.tracePath}\`` : "";
+        const detail = (stderr ? `\n\n\`\`\`\n${stderr}\n\`\`\`` : "") + trace;
+        setMessages((prev) => [...prev, {
+          role: "assistant",
+          content: `⚠️ The agent process exited${e.payload.exitStatus ? ` (${e.payload.exitStatus})` : ""}. Restarting…${detail}`,
+        }]);
+      }),
+      listen<RestartEvent>("acp-restarted", (e) => {
+        const { sessionId, interruptedPrompt } = e.payload;
+        if (!interruptedPrompt) return;
+        interruptedRef.current.add(sessionId);
+        if (sessionId === currentSessionRef.current) offerRetry(sessionId);
+      }),
+      listen<RestartEvent>("acp-restart-failed", (e) => {
+        if (e.payload.sessionId !== currentSessionRef.current) return;
+        setMessages((prev) => [...prev, { role: "assistant", content: `❌ Could not restart the agent: ${errorText(e.payload.error)}` }]);
+      }),
+    ];
+    return () => { pending.forEach((p) => p.then((unlisten) => unlisten())); };
+  }, [offerRetry]);
+
+  // A prompt interrupted in another tab waits until that tab is opened
+  useEffect(() => {
+    if (currentSessionId && interruptedRef.current.has(currentSessionId)) offerRetry(currentSessionId);
+  }, [currentSessionId, offerRetry]);
+
+  const respondPermission = useCallback(async (req: PermissionRequest, optionId: string | null) => {
+    setPermissionRequests((prev) => prev.filter((p) => p !== req));
+    try {
+      await invoke("respond_permission", { sessionId: req.sessionId, requestId: req.requestId, optionId });
+    } catch (e) {
+      console.error("Failed to answer permission request", e);
+    }
+  }, []);
+
   const cancelPrompt = useCallback(async () => {
     const sid = currentSessionRef.current;
     if (!sid) return;
@@ -222,24 +617,38 @@ export default function App() {
     }
   }, []);
 
+  const openSettings = useCallback(async () => {
+    try {
+      const settings = await invoke<AppSettings>("get_settings");
+      setAppSettings(settings);
+      setCliSettings(settings.kiroCli);
+      setAgentsJson(settings.agents.length ? JSON.stringify(settings.agents, null, 2) : "");
+      setCliCheck("");
+      setShowSettings(true);
+    } catch (e) {
+      console.error("Failed to load settings", e);
+    }
+  }, []);
+
   const ensureSession = useCallback(async (): Promise<string | null> => {
     if (currentSessionRef.current) return currentSessionRef.current;
     try {
-      const sessionId = await invoke<string>("new_acp_session");
+      const sessionId = await invoke<string>("new_acp_session", { agentId: selectedAgent });
       setCurrentSessionId(sessionId);
       const label = `${new Date().toLocaleString()} · ${sessionId.slice(0, 8)}`;
       const ctx = Array.from(contextFiles);
-      setSessions((prev) => [...prev, { id: sessionId, label, messages: [], contextFiles: ctx }]);
+      setSessions((prev) => [...prev, { id: sessionId, label, messages: [], contextFiles: ctx, agentId: selectedAgent ?? undefined }]);
       sentContextRef.current = new Set();
       if (selectedModel !== "auto") {
         await invoke("set_model", { sessionId, modelId: selectedModel }).catch(() => {});
       }
       return sessionId;
     } catch (e) {
-      setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to create session: ${e}` }]);
+      setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to create session: ${errorText(e)}` }]);
+      if (isError(e, "cliNotFound")) openSettings();
       return null;
     }
-  }, [selectedModel, contextFiles]);
+  }, [selectedModel, selectedAgent, contextFiles, openSettings]);
 
   const newSession = useCallback(async () => {
     if (loading) return;
@@ -247,19 +656,21 @@ export default function App() {
     const curSent = Array.from(sentContextRef.current);
     setSessions((prev) => prev.map((s) => s.id === currentSessionRef.current ? { ...s, messages, contextFiles: curCtx, sentContextFiles: curSent } : s));
     try {
-      const sessionId = await invoke<string>("new_acp_session");
+      const sessionId = await invoke<string>("new_acp_session", { agentId: selectedAgent });
+      setReadOnlySession(null);
       setCurrentSessionId(sessionId);
       const label = `${new Date().toLocaleString()} · ${sessionId.slice(0, 8)}`;
-      setSessions((prev) => [...prev, { id: sessionId, label, messages: [], contextFiles: curCtx }]);
+      setSessions((prev) => [...prev, { id: sessionId, label, messages: [], contextFiles: curCtx, agentId: selectedAgent ?? undefined }]);
       setMessages([]);
       sentContextRef.current = new Set();
       if (selectedModel !== "auto") {
         await invoke("set_model", { sessionId, modelId: selectedModel }).catch(() => {});
       }
     } catch (e) {
-      setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to create session: ${e}` }]);
+      setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to create session: ${errorText(e)}` }]);
+      if (isError(e, "cliNotFound")) openSettings();
     }
-  }, [loading, messages, selectedModel, contextFiles]);
+  }, [loading, messages, selectedModel, selectedAgent, contextFiles, openSettings]);
 
   const switchSession = useCallback(
     (sessionId: string) => {
@@ -269,6 +680,7 @@ export default function App() {
       setSessions((prev) => prev.map((s) => s.id === currentSessionRef.current ? { ...s, messages, contextFiles: curCtx, sentContextFiles: curSent } : s));
       const target = sessions.find((s) => s.id === sessionId);
       if (target) {
+        setReadOnlySession(null);
         setCurrentSessionId(sessionId);
         setMessages(target.messages);
         setContextFiles(new Set(target.contextFiles || []));
@@ -282,6 +694,7 @@ export default function App() {
     (sessionId: string, e: React.MouseEvent) => {
       e.stopPropagation();
       invoke("close_acp_session", { sessionId }).catch(() => {});
+      interruptedRef.current.delete(sessionId);
       const remaining = sessions.filter((s) => s.id !== sessionId);
       setSessions(remaining);
       if (sessionId === currentSessionRef.current) {
@@ -305,13 +718,14 @@ export default function App() {
   const loadHistory = useCallback(async () => {
     try {
       const data = await invoke<string>("load_session_history");
-      const list = JSON.parse(data) as Array<{ sessionId: string; label: string; messages: ChatMessage[]; contextFiles?: string[]; updatedAt: string }>;
+      const list = JSON.parse(data) as Array<{ sessionId: string; label: string; messages: ChatMessage[]; contextFiles?: string[]; agentId?: string; updatedAt: string }>;
       setHistoryList(list.map((h) => ({
         id: h.sessionId,
         label: h.updatedAt ? `${new Date(h.updatedAt).toLocaleString()} · ${h.sessionId.slice(0, 8)}` : h.label,
         messages: h.messages,
         contextFiles: h.contextFiles,
         sentContextFiles: h.contextFiles,
+        agentId: h.agentId,
       })));
       setShowHistory(true);
     } catch (e) {
@@ -319,11 +733,47 @@ export default function App() {
     }
   }, []);
 
-  const loadHistorySession = useCallback(
-    async (session: ChatSession) => {
+  const refreshAgents = useCallback(async () => {
+    try {
+      setAgents(await invoke<AgentProfile[]>("list_agents"));
+    } catch (e) {
+      console.error("Failed to list agents", e);
+    }
+  }, []);
+
+  useEffect(() => { refreshAgents(); }, [refreshAgents]);
+
+  const checkCli = useCallback(async () => {
+    if (!cliSettings) return;
+    setCliCheck("Checking…");
+    try {
+      const result = await invoke<CliCheck>("check_kiro_cli", { settings: cliSettings });
+      const agent = result.agentInfo ? `${result.agentInfo.title || result.agentInfo.name} ${result.agentInfo.version}` : "Unknown agent";
+      setCliCheck(`✅ ${agent} (protocol v${result.protocolVersion})\n${result.path} ${result.args.join(" ")}\n${JSON.stringify(result.agentCapabilities)}`);
+    } catch (e) {
+      setCliCheck(`❌ ${errorText(e)}`);
+    }
+  }, [cliSettings]);
+
+  const saveSettings = useCallback(async () => {
+    if (!cliSettings || !appSettings) return;
+    try {
+      const customAgents = agentsJson.trim() ? JSON.parse(agentsJson) as AgentProfile[] : [];
+      await invoke("save_settings", { settings: { ...appSettings, kiroCli: cliSettings, agents: customAgents } });
+      setShowSettings(false);
+      refreshAgents();
+    } catch (e) {
+      setCliCheck(`❌ ${errorText(e)}`);
+    }
+  }, [cliSettings, appSettings, agentsJson, refreshAgents]);
+
+  // Typed explicitly because it calls itself to take a locked session over
+  const loadHistorySession: (session: ChatSession, takeOver?: boolean) => Promise<void> = useCallback(
+    async (session: ChatSession, takeOver = false) => {
       try {
-        const newId = await invoke<string>("load_acp_session", { sessionId: session.id });
+        const newId = await invoke<string>("load_acp_session", { sessionId: session.id, agentId: session.agentId ?? null, takeOver });
         const loaded = { ...session, id: newId };
+        setReadOnlySession(null);
         setCurrentSessionId(newId);
         setMessages(session.messages);
         if (session.contextFiles?.length) {
@@ -339,14 +789,26 @@ export default function App() {
         }
         setShowHistory(false);
       } catch (e) {
-        setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to load session: ${e}` }]);
+        if (isError(e, "sessionLocked")) {
+          const owner = `another Kiro process${e.pid ? ` (PID ${e.pid}${e.startedAt ? `, started ${new Date(e.startedAt).toLocaleString()}` : ""})` : ""}`;
+          if (window.confirm(`This session is open in ${owner}.\n\nOK: take it over. Close the other process first, or both will write to it.\nCancel: read it from history without the agent.`)) {
+            loadHistorySession(session, true);
+            return;
+          }
+          setCurrentSessionId(null);
+          setReadOnlySession(session);
+          setMessages(session.messages);
+          setShowHistory(false);
+          return;
+        }
+        setMessages((prev) => [...prev, { role: "assistant", content: `❌ Failed to load session: ${errorText(e)}` }]);
       }
     },
     [sessions],
   );
 
   const sendMessage = useCallback(async () => {
-    if (!input.trim() || loading) return;
+    if (!input.trim() || loading || readOnlySession) return;
     const userMsg = input.trim();
     setInput("");
     setMessages((prev) => [...prev, { role: "user", content: userMsg }]);
@@ -357,7 +819,7 @@ export default function App() {
     const unsent = Array.from(contextFiles).filter((f) => !sentContextRef.current.has(f));
     unsent.forEach((f) => sentContextRef.current.add(f));
     await runPrompt(sessionId, userMsg, unsent);
-  }, [input, loading, contextFiles, runPrompt, ensureSession]);
+  }, [input, loading, readOnlySession, contextFiles, runPrompt, ensureSession]);
 
   if (!workspace) {
     return (
@@ -383,11 +845,41 @@ export default function App() {
           value={selectedModel}
           onChange={(e) => changeModel(e.target.value)}
         >
-          {MODELS.map((m) => (
-            <option key={m.id} value={m.id}>{m.label} ({m.credits})</option>
-          ))}
+          {capabilities?.models?.availableModels.length
+            ? capabilities.models.availableModels.map((m) => (
+              <option key={m.modelId} value={m.modelId} title={m.description}>{m.name}</option>
+            ))
+            : MODELS.map((m) => (
+              <option key={m.id} value={m.id}>{m.label} ({m.credits})</option>
+            ))}
         </select>
+        {capabilities?.modes && capabilities.modes.availableModes.length > 1 && (
+          <select
+            className="model-select"
+            value={capabilities.modes.currentModeId}
+            onChange={(e) => changeMode(e.target.value)}
+            disabled={loading}
+          >
+            {capabilities.modes.availableModes.map((m) => (
+              <option key={m.id} value={m.id} title={m.description}>{m.name}</option>
+            ))}
+          </select>
+        )}
+        {agents.length > 1 && (
+          <select
+            className="model-select"
+            value={selectedAgent ?? ""}
+            onChange={(e) => setSelectedAgent(e.target.value || null)}
+            title="Agent for new chats"
+          >
+            <option value="">Default agent</option>
+            {agents.map((a) => (
+              <option key={a.id} value={a.id}>{a.name}</option>
+            ))}
+          </select>
+        )}
         <button onClick={openWorkspace} className="btn-small">Switch</button>
+        <button onClick={openSettings} className="btn-small" title="Kiro CLI settings"><SettingsIcon size={12} /></button>
       </header>
 
       <div className="panels">
@@ -426,9 +918,11 @@ export default function App() {
               {selectedFile ? (
                 selectedFile.extension === "pdf" ? (
                   <iframe src={convertFileSrc(selectedFile.path) + "#view=FitH"} className="preview-iframe" />
-                ) : selectedFile.extension === "html" ? (
+                ) : HTML_EXTENSIONS.includes(selectedFile.extension) ? (
                   <iframe src={convertFileSrc(selectedFile.path)} className="preview-iframe" />
-                ) : selectedFile.extension === "md" || selectedFile.extension === "docx" ? (
+                ) : IMAGE_EXTENSIONS.includes(selectedFile.extension) ? (
+                  <img src={convertFileSrc(selectedFile.path)} alt={selectedFile.name} className="preview-image" />
+                ) : ["md", "docx", "epub"].includes(selectedFile.extension) ? (
                   <div className="markdown-body"><ReactMarkdown remarkPlugins={[remarkGfm]}>{fileContent}</ReactMarkdown></div>
                 ) : (
                   <pre className="text-content">{fileContent}</pre>
@@ -515,6 +1009,116 @@ export default function App() {
                 </div>
               </div>
             )}
+            {showSettings && cliSettings && (
+              <div className="history-panel">
+                <div className="history-header">
+                  <span>Settings</span>
+                  <button className="btn-small" onClick={() => setShowSettings(false)}><X size={12} /></button>
+                </div>
+                <div className="settings-form">
+                  <label>
+                    Binary path
+                    <input
+                      value={cliSettings.path ?? ""}
+                      placeholder="Search the usual install locations"
+                      onChange={(e) => setCliSettings({ ...cliSettings, path: e.target.value || null })}
+                    />
+                  </label>
+                  <label>
+                    Extra arguments (one per line, after <code>acp</code>)
+                    <textarea
+                      rows={3}
+                      value={cliSettings.args.join("\n")}
+                      onChange={(e) => setCliSettings({ ...cliSettings, args: e.target.value.split("\n").filter((a) => a.trim()) })}
+                    />
+                  </label>
+                  <label>
+                    Environment (<code>KEY=value</code> per line)
+                    <textarea
+                      rows={3}
+                      value={Object.entries(cliSettings.env).map(([k, v]) => `${k}=${v}`).join("\n")}
+                      onChange={(e) => setCliSettings({
+                        ...cliSettings,
+                        env: Object.fromEntries(e.target.value.split("\n")
+                          .filter((line) => line.includes("="))
+                          .map((line) => [line.slice(0, line.indexOf("=")).trim(), line.slice(line.indexOf("=") + 1)])),
+                      })}
+                    />
+                  </label>
+                  <label>
+                    Working directory
+                    <input
+                      value={cliSettings.workingDir ?? ""}
+                      placeholder="The workspace"
+                      onChange={(e) => setCliSettings({ ...cliSettings, workingDir: e.target.value || null })}
+                    />
+                  </label>
+                  <label>
+                    Other ACP agents (JSON list of <code>{"{"}id, name, command, args, env{"}"}</code>)
+                    <textarea
+                      rows={5}
+                      value={agentsJson}
+                      placeholder='[{ "id": "gemini", "name": "Gemini CLI", "command": "gemini", "args": ["--experimental-acp"] }]'
+                      onChange={(e) => setAgentsJson(e.target.value)}
+                    />
+                  </label>
+                  {appSettings && (
+                    <label>
+                      Default agent
+                      <select
+                        value={appSettings.defaultAgent ?? ""}
+                        onChange={(e) => setAppSettings({ ...appSettings, defaultAgent: e.target.value || null })}
+                      >
+                        {agents.map((a) => (
+                          <option key={a.id} value={a.id === "kiro" ? "" : a.id}>{a.name}</option>
+                        ))}
+                      </select>
+                    </label>
+                  )}
+                  {appSettings && (
+                    <>
+                      <label>
+                        Warm agent processes kept ready for new chats
+                        <input
+                          type="number"
+                          min={0}
+                          value={appSettings.poolSize}
+                          onChange={(e) => setAppSettings({ ...appSettings, poolSize: Math.max(0, Number(e.target.value) || 0) })}
+                        />
+                      </label>
+                      <label>
+                        Max agent processes (idle chats beyond this are suspended, 0 = no limit)
+                        <input
+                          type="number"
+                          min={0}
+                          value={appSettings.maxLiveProcesses}
+                          onChange={(e) => setAppSettings({ ...appSettings, maxLiveProcesses: Math.max(0, Number(e.target.value) || 0) })}
+                        />
+                      </label>
+                      {(Object.keys(TIMEOUT_LABELS) as TimeoutKey[]).map((key) => (
+                        <label key={key}>
+                          {TIMEOUT_LABELS[key]} (seconds, 0 = wait forever)
+                          <input
+                            type="number"
+                            min={0}
+                            value={appSettings.timeouts[key]}
+                            onChange={(e) => setAppSettings({
+                              ...appSettings,
+                              timeouts: { ...appSettings.timeouts, [key]: Math.max(0, Number(e.target.value) || 0) },
+                            })}
+                          />
+                        </label>
+                      ))}
+                    </>
+                  )}
+                  <div className="settings-actions">
+                    <button className="btn-small" onClick={checkCli}>Check Kiro CLI</button>
+                    <button className="btn-accent" onClick={saveSettings}>Save</button>
+                  </div>
+                  {cliCheck && <pre className="settings-check">{cliCheck}</pre>}
+                </div>
+              </div>
+            )}
             <div className="chat-messages">
               {messages.map((msg, i) => (
                 <div key={i} className={`message ${msg.role}`}>
@@ -525,6 +1129,25 @@ export default function App() {
                   </div>
                 </div>
               ))}
+              {permissionRequests.filter((p) => p.sessionId === currentSessionId).map((p) => (
+                <div key={String(p.requestId)} className="message status">
+                  <div className="status-bubble permission-bubble">
+                    <Wrench size={12} /> {p.toolCall.title || `Allow ${p.kind} tool?`}
+                    {p.options.map((o) => (
+                      <button key={o.optionId} className="btn-small" onClick={() => respondPermission(p, o.optionId)}>{o.name}</button>
+                    ))}
+                  </div>
+                </div>
+              ))}
+              {loading && stalled && stalled.sessionId === currentSessionId && (
+                <div className="message status">
+                  <div className="status-bubble permission-bubble">
+                    No response from the agent for {stalled.idleSeconds}s
+                    <button className="btn-small" onClick={cancelPrompt}>Cancel</button>
+                    <button className="btn-small" onClick={() => restartAgent(stalled.sessionId)}>Restart agent</button>
+                  </div>
+                </div>
+              )}
               {loading && statusText && (
                 <div className="message status"><div className="status-bubble"><Wrench size={12} className="spin" /> {statusText}</div></div>
               )}
@@ -535,11 +1158,17 @@ export default function App() {
                 value={input}
                 onChange={(e) => setInput(e.target.value)}
                 onKeyDown={(e) => { if (e.key === "Enter" && !e.shiftKey) { e.preventDefault(); sendMessage(); } }}
-                placeholder="Ask about your documents..."
-                disabled={loading}
+                placeholder={readOnlySession ? "Read-only: another process has this session open" : "Ask about your documents..."}
+                disabled={loading || !!readOnlySession}
                 rows={2}
               />
-              <button onClick={sendMessage} disabled={loading || !input.trim()} className="btn-send"><ArrowUp size={16} /></button>
+              {readOnlySession && (
+                <button
+                  className="btn-small"
+                  onClick={() => window.confirm("Take the session over from the other process? Close it first, or both will write to the session.") && loadHistorySession(readOnlySession, true)}
+                >Take over</button>
+              )}
+              <button onClick={sendMessage} disabled={loading || !!readOnlySession || !input.trim()} className="btn-send"><ArrowUp size={16} /></button>
               {loading && <button onClick={cancelPrompt} className="btn-cancel"><Square size={14} /></button>}
             </div>
           </div>